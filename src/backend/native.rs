@@ -1,6 +1,26 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+
+use crate::cancel::Cancellation;
+use crate::descriptor::{self, DescriptorCache};
+use crate::filter;
+use crate::timer::{self, Deadline};
 use crate::usb::{
-    ControlIn, ControlOut, ControlType, UsbDeviceInfo, UsbDevice, UsbInterface, Recipient, Error,
+    Capabilities, ControlIn, ControlOut, ControlType, UsbDeviceInfo, UsbDevice, UsbInterface, Recipient, Error,
+    AltSettingInfo, DeviceInfoSnapshot, Direction, EndpointInfo, GetDeviceOptions, InterfaceFilterInfo, PermissionState,
+    RetryPolicy, Speed,
+    TransferResult, TransferStatus, TransferType, Version,
 };
+use crate::watch::disconnect_error;
+
+// How long to wait for the device to answer a request for a descriptor
+const DESCRIPTOR_TIMEOUT: Duration = Duration::from_secs(1);
 
 #[derive(Clone, Debug)]
 pub struct DeviceInfo {
@@ -11,6 +31,11 @@ pub struct DeviceInfo {
 pub struct Device {
     device_info: DeviceInfo,
     device: nusb::Device,
+    claims: Arc<AtomicUsize>,
+    open: Arc<AtomicBool>,
+    // Cleared once the device is known to have been unplugged
+    connected: Arc<AtomicBool>,
+    descriptor_cache: DescriptorCache,
 }
 
 impl std::fmt::Debug for Device {
@@ -23,6 +48,17 @@ impl std::fmt::Debug for Device {
 pub struct Interface {
     interface: nusb::Interface,
     number: u8,
+    cancellation: Cancellation,
+    default_timeout: Option<Duration>,
+    retry: RetryPolicy,
+    in_flight: HashMap<u8, usize>,
+    // nusb doesn't keep track of the selected alternate setting
+    alt_setting: Arc<AtomicU8>,
+    speed: Option<Speed>,
+    connected: Arc<AtomicBool>,
+    // Shared with the device, so strings read by either are cached once
+    descriptor_cache: DescriptorCache,
+    _claim: Claim,
 }
 
 impl std::fmt::Debug for Interface {
@@ -31,16 +67,48 @@ impl std::fmt::Debug for Interface {
     }
 }
 
-#[derive(PartialEq, Clone, Default)]
+// Counts the open interfaces of a device, which is needed because the OS
+// refuses to change the configuration while any of them are claimed
+struct Claim(Arc<AtomicUsize>);
+
+impl Claim {
+    fn new(claims: &Arc<AtomicUsize>) -> Self {
+        claims.fetch_add(1, Ordering::AcqRel);
+        Self(claims.clone())
+    }
+}
+
+impl Clone for Claim {
+    fn clone(&self) -> Self {
+        Self::new(&self.0)
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct DeviceFilter {
     pub vendor_id: Option<u16>,
     pub product_id: Option<u16>,
     pub class: Option<u8>,
     pub subclass: Option<u8>,
     pub protocol: Option<u8>,
+    pub serial_number: Option<String>,
+    pub manufacturer_string_contains: Option<String>,
+    pub product_string_contains: Option<String>,
+    pub device_version_min: Option<Version>,
+    pub device_version_max: Option<Version>,
+    pub bus_number: Option<u8>,
+    pub port_chain: Option<Vec<u8>>,
+    pub exclude: bool,
 }
 
 impl DeviceFilter {
+    #[deprecated(note = "use `DeviceFilter::builder` instead")]
     pub fn new(
         vendor_id: Option<u16>,
         product_id: Option<u16>,
@@ -54,6 +122,14 @@ impl DeviceFilter {
             class,
             subclass,
             protocol,
+            serial_number: None,
+            manufacturer_string_contains: None,
+            product_string_contains: None,
+            device_version_min: None,
+            device_version_max: None,
+            bus_number: None,
+            port_chain: None,
+            exclude: false,
         }
     }
 }
@@ -61,96 +137,172 @@ impl DeviceFilter {
 pub async fn get_device(
     device_filters: Vec<DeviceFilter>
 ) -> Result<DeviceInfo, Error> {
-    let devices = nusb::list_devices().unwrap();
+    if !filter::includes_any(&device_filters) {
+        return Err(Error::DeviceNotFound);
+    }
 
-    let mut device_info = None;
-    for prelim_dev_inf in devices {
-        // See if the device exists in the list
-        if device_filters.iter().any(|info| {
-            let mut result = false;
+    match filter::best(&device_filters, list_devices().await?).await {
+        Some(device_info) => Ok(device_info),
+        None => Err(Error::DeviceNotFound),
+    }
+}
 
-            if info.vendor_id.is_some() {
-                result = info.vendor_id.unwrap() == prelim_dev_inf.vendor_id();
-            }
+// There is no chooser to show on native, so the options make no difference
+pub async fn get_device_with_options(
+    device_filters: Vec<DeviceFilter>,
+    _options: GetDeviceOptions,
+) -> Result<DeviceInfo, Error> {
+    get_device(device_filters).await
+}
 
-            if info.product_id.is_some() {
-                result = info.product_id.unwrap() == prelim_dev_inf.product_id();
-            }
+// There is no chooser to show on native, so this finds the device directly
+pub async fn request_device(device_filters: Vec<DeviceFilter>) -> Result<DeviceInfo, Error> {
+    get_device(device_filters).await
+}
 
-            if info.class.is_some() {
-                result = info.class.unwrap() == prelim_dev_inf.class();
-            }
+// There is no chooser on native, so there is nothing to do until the device
+// is waited for
+#[derive(Debug, Clone)]
+pub(crate) struct PendingRequest {
+    device_filters: Vec<DeviceFilter>,
+}
 
-            if info.subclass.is_some() {
-                result = info.subclass.unwrap() == prelim_dev_inf.subclass();
-            }
+impl PendingRequest {
+    pub(crate) fn new(device_filters: Vec<DeviceFilter>) -> Self {
+        Self { device_filters }
+    }
 
-            if info.protocol.is_some() {
-                result = info.protocol.unwrap() == prelim_dev_inf.protocol();
-            }
+    pub(crate) fn trigger(&self) {}
 
-            result
-        }) {
-            device_info = Some(prelim_dev_inf);
-            break;
+    pub(crate) async fn device(&self) -> Result<DeviceInfo, Error> {
+        get_device(self.device_filters.clone()).await
+    }
+}
+
+pub async fn get_device_with(
+    device_filters: Vec<DeviceFilter>,
+    predicate: impl Fn(&DeviceInfoSnapshot) -> bool,
+) -> Result<DeviceInfo, Error> {
+    for device_info in list_devices().await? {
+        if filter::accepts(&device_filters, &device_info, &predicate).await {
+            return Ok(device_info);
         }
     }
 
-    let device_info = match device_info {
-        Some(dev) => dev,
-        None => return Err(Error::DeviceNotFound),
+    Err(Error::DeviceNotFound)
+}
+
+// Every native platform nusb builds for can use USB devices
+pub fn is_supported() -> bool {
+    true
+}
+
+// Native programs don't need permission to see devices, though opening them
+// may still fail if the OS doesn't allow it
+pub async fn permission_state() -> Result<PermissionState, Error> {
+    Ok(PermissionState::Granted)
+}
+
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        enumerate_without_prompt: true,
+        detach_kernel_driver: cfg!(target_os = "linux"),
+        abort_transfers: true,
+        speed: cfg!(any(target_os = "linux", target_os = "macos", target_os = "windows")),
+        usb_version: cfg!(target_os = "linux"),
+        port_chain: cfg!(any(target_os = "linux", target_os = "macos")),
+        device_control_transfers: cfg!(any(target_os = "linux", target_os = "macos", target_os = "android")),
+        hotplug: true,
+        interrupt_transfers: false,
+        isochronous_transfers: false,
+    }
+}
+
+pub async fn list_devices() -> Result<Vec<DeviceInfo>, Error> {
+    let mut devices: Vec<DeviceInfo> = match nusb::list_devices() {
+        Ok(devices) => devices.map(|device_info| DeviceInfo { device_info }).collect(),
+        Err(err) => return Err(Error::CommunicationError(err.to_string())),
+    };
+
+    // Enumeration order differs between runs and platforms, so list devices
+    // by where they are, which also puts any duplicates next to each other
+    let location = |device: &DeviceInfo| {
+        (device.device_info.bus_number(), device.device_info.device_address())
     };
+    devices.sort_by_key(location);
+    devices.dedup_by_key(|device| location(device));
 
-    Ok(DeviceInfo { device_info })
+    Ok(devices)
 }
 
 pub async fn get_device_list(
     device_filters: Vec<DeviceFilter>,
-) -> Result<impl Iterator<Item = DeviceInfo>, Error> {
-    let devices_info = nusb::list_devices().unwrap();
+) -> Result<Vec<DeviceInfo>, Error> {
+    let devices = filter::get_paired_devices(device_filters).await?;
+    if devices.is_empty() {
+        return Err(Error::DeviceNotFound);
+    }
+
+    Ok(devices)
+}
 
+pub async fn get_device_list_with(
+    device_filters: Vec<DeviceFilter>,
+    predicate: impl Fn(&DeviceInfoSnapshot) -> bool,
+) -> Result<Vec<DeviceInfo>, Error> {
     let mut devices = Vec::new();
-    for prelim_dev_inf in devices_info {
-        // See if the device exists in the list
-        if device_filters.iter().any(|info| {
-            let mut result = false;
+    for device_info in list_devices().await? {
+        if filter::accepts(&device_filters, &device_info, &predicate).await {
+            devices.push(device_info);
+        }
+    }
 
-            if info.vendor_id.is_some() {
-                result = info.vendor_id.unwrap() == prelim_dev_inf.vendor_id();
-            }
+    if devices.is_empty() {
+        return Err(Error::DeviceNotFound);
+    }
 
-            if info.product_id.is_some() {
-                result = info.product_id.unwrap() == prelim_dev_inf.product_id();
-            }
+    Ok(devices)
+}
 
-            if info.class.is_some() {
-                result = info.class.unwrap() == prelim_dev_inf.class();
-            }
+// Identifies a connected device in hotplug events, as a disconnected device
+// can't be asked for its details any more
+pub(crate) type DeviceKey = nusb::DeviceId;
 
-            if info.subclass.is_some() {
-                result = info.subclass.unwrap() == prelim_dev_inf.subclass();
-            }
+pub(crate) enum HotplugEvent {
+    Connected(DeviceInfo),
+    Disconnected(DeviceKey),
+}
 
-            if info.protocol.is_some() {
-                result = info.protocol.unwrap() == prelim_dev_inf.protocol();
-            }
+// Devices being connected and disconnected, from nusb's watcher, which stops
+// watching when this is dropped
+pub(crate) struct Hotplug {
+    watch: nusb::hotplug::HotplugWatch,
+}
 
-            result
-        }) {
-            devices.push(prelim_dev_inf);
+impl Hotplug {
+    pub(crate) fn new() -> Result<Self, Error> {
+        match nusb::watch_devices() {
+            Ok(watch) => Ok(Self { watch }),
+            Err(err) => Err(Error::CommunicationError(err.to_string())),
         }
     }
 
-    if devices.is_empty() {
-        return Err(Error::DeviceNotFound);
+    pub(crate) fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<HotplugEvent>> {
+        Pin::new(&mut self.watch).poll_next(cx).map(|event| {
+            event.map(|event| match event {
+                nusb::hotplug::HotplugEvent::Connected(device_info) => {
+                    HotplugEvent::Connected(DeviceInfo { device_info })
+                }
+                nusb::hotplug::HotplugEvent::Disconnected(key) => HotplugEvent::Disconnected(key),
+            })
+        })
     }
+}
 
-    let devices_opened: Vec<DeviceInfo> = devices
-        .into_iter()
-        .map(|d| DeviceInfo { device_info: d })
-        .collect();
-
-    Ok(devices_opened.into_iter())
+impl DeviceInfo {
+    pub(crate) fn key(&self) -> DeviceKey {
+        self.device_info.id()
+    }
 }
 
 impl UsbDeviceInfo for DeviceInfo {
@@ -161,6 +313,10 @@ impl UsbDeviceInfo for DeviceInfo {
             Ok(dev) => Ok(Self::Device {
                 device_info: self,
                 device: dev,
+                claims: Arc::new(AtomicUsize::new(0)),
+                open: Arc::new(AtomicBool::new(true)),
+                connected: Arc::new(AtomicBool::new(true)),
+                descriptor_cache: DescriptorCache::default(),
             }),
             Err(err) => Err(Error::CommunicationError(err.to_string())),
         }
@@ -182,6 +338,10 @@ impl UsbDeviceInfo for DeviceInfo {
         self.device_info.subclass()
     }
 
+    async fn protocol(&self) -> u8 {
+        self.device_info.protocol()
+    }
+
     async fn manufacturer_string(&self) -> Option<String> {
         self.device_info.manufacturer_string().map(str::to_string)
     }
@@ -189,6 +349,115 @@ impl UsbDeviceInfo for DeviceInfo {
     async fn product_string(&self) -> Option<String> {
         self.device_info.product_string().map(str::to_string)
     }
+
+    async fn serial_number_string(&self) -> Option<String> {
+        self.device_info.serial_number().map(str::to_string)
+    }
+
+    async fn device_version(&self) -> Version {
+        Version::from_bcd(self.device_info.device_version())
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn usb_version(&self) -> Option<Version> {
+        // sysfs formats bcdUSB as "%2x.%02x"
+        let version = std::fs::read_to_string(self.device_info.sysfs_path().join("version")).ok()?;
+        let (major, minor) = version.trim().split_once('.')?;
+
+        let major = u8::from_str_radix(major.trim(), 16).ok()?;
+        let minor = u8::from_str_radix(minor, 16).ok()?;
+
+        Some(Version::from_bcd(u16::from_be_bytes([major, minor])))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn usb_version(&self) -> Option<Version> {
+        None
+    }
+
+    async fn speed(&self) -> Option<Speed> {
+        match self.device_info.speed()? {
+            nusb::Speed::Low => Some(Speed::Low),
+            nusb::Speed::Full => Some(Speed::Full),
+            nusb::Speed::High => Some(Speed::High),
+            nusb::Speed::Super => Some(Speed::Super),
+            nusb::Speed::SuperPlus => Some(Speed::SuperPlus),
+            _ => None,
+        }
+    }
+
+    async fn bus_number(&self) -> Option<u8> {
+        Some(self.device_info.bus_number())
+    }
+
+    async fn device_address(&self) -> Option<u8> {
+        Some(self.device_info.device_address())
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn port_chain(&self) -> Option<Vec<u8>> {
+        // Devices are named like "1-2.3", the bus followed by the ports
+        let name = self.device_info.sysfs_path().file_name()?.to_str()?;
+        let (_, ports) = name.split_once('-')?;
+
+        ports.split('.').map(|port| port.parse().ok()).collect()
+    }
+
+    #[cfg(target_os = "macos")]
+    async fn port_chain(&self) -> Option<Vec<u8>> {
+        // The location ID holds the bus in the top byte, followed by one
+        // nibble per port until a zero nibble
+        let location = self.device_info.location_id();
+
+        let ports: Vec<u8> = (0..6)
+            .map(|hop| ((location >> (20 - hop * 4)) & 0x0F) as u8)
+            .take_while(|port| *port != 0)
+            .collect();
+
+        if ports.is_empty() {
+            return None;
+        }
+
+        Some(ports)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    async fn port_chain(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn platform_path(&self) -> Option<String> {
+        Some(self.device_info.sysfs_path().display().to_string())
+    }
+
+    #[cfg(target_os = "windows")]
+    async fn platform_path(&self) -> Option<String> {
+        Some(self.device_info.instance_id().to_string_lossy().into_owned())
+    }
+
+    #[cfg(target_os = "macos")]
+    async fn platform_path(&self) -> Option<String> {
+        Some(format!("{:#010x}", self.device_info.location_id()))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    async fn platform_path(&self) -> Option<String> {
+        None
+    }
+
+    async fn interfaces(&self) -> Vec<InterfaceFilterInfo> {
+        self.device_info
+            .interfaces()
+            .map(|interface| InterfaceFilterInfo {
+                number: interface.interface_number(),
+                class: interface.class(),
+                subclass: interface.subclass(),
+                protocol: interface.protocol(),
+                interface_string: interface.interface_string().map(str::to_string),
+            })
+            .collect()
+    }
 }
 
 impl UsbDevice for Device {
@@ -197,30 +466,105 @@ impl UsbDevice for Device {
     async fn open_interface(&self, number: u8) -> Result<Self::Interface, Error> {
         let interface = match self.device.claim_interface(number) {
             Ok(inter) => inter,
-            Err(err) => return Err(Error::CommunicationError(err.to_string())),
+            Err(err) => return Err(self.claim_error(number, err).await),
         };
 
         Ok(Interface {
             interface,
-            number
+            number,
+            cancellation: Cancellation::default(),
+            default_timeout: None,
+            retry: RetryPolicy::default(),
+            in_flight: HashMap::new(),
+            alt_setting: Arc::new(AtomicU8::new(0)),
+            speed: self.device_info.speed().await,
+            connected: self.connected.clone(),
+            descriptor_cache: self.descriptor_cache.clone(),
+            _claim: Claim::new(&self.claims),
         })
     }
 
     async fn detach_and_open_interface(&self, number: u8) -> Result<Self::Interface, Error> {
         let interface = match self.device.detach_and_claim_interface(number) {
             Ok(inter) => inter,
-            Err(err) => return Err(Error::CommunicationError(err.to_string())),
+            Err(err) => return Err(self.claim_error(number, err).await),
         };
 
         Ok(Interface {
             interface,
-            number
+            number,
+            cancellation: Cancellation::default(),
+            default_timeout: None,
+            retry: RetryPolicy::default(),
+            in_flight: HashMap::new(),
+            alt_setting: Arc::new(AtomicU8::new(0)),
+            speed: self.device_info.speed().await,
+            connected: self.connected.clone(),
+            descriptor_cache: self.descriptor_cache.clone(),
+            _claim: Claim::new(&self.claims),
         })
     }
 
+    async fn active_configuration(&self) -> Result<Option<u8>, Error> {
+        // This only fails if the device is unconfigured, or the OS reports a
+        // configuration which has no descriptor, which can't be used either
+        match self.device.active_configuration() {
+            Ok(configuration) => Ok(Some(configuration.configuration_value())),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn set_configuration(&self, value: u8) -> Result<(), Error> {
+        if self.active_configuration().await? == Some(value) {
+            return Ok(());
+        }
+
+        if self.claims.load(Ordering::Acquire) > 0 {
+            return Err(Error::InterfacesClaimed);
+        }
+
+        self.descriptor_cache.clear();
+        match self.device.set_configuration(value) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::CommunicationError(err.to_string())),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn kernel_driver(&self, interface: u8) -> Result<Option<String>, Error> {
+        let Some(configuration) = self.active_configuration().await? else {
+            return Ok(None);
+        };
+
+        // Interfaces are in sysfs as "<device>:<configuration>.<interface>",
+        // with a link to the driver bound to them
+        let path = self.device_info.device_info.sysfs_path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return Ok(None);
+        };
+        let driver = path.join(format!("{name}:{configuration}.{interface}")).join("driver");
+
+        match std::fs::read_link(driver) {
+            Ok(target) => Ok(target
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::CommunicationError(err.to_string())),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn kernel_driver(&self, _interface: u8) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+
     async fn reset(&self) -> Result<(), Error> {
+        self.descriptor_cache.clear();
         match self.device.reset() {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.open.store(false, Ordering::Release);
+                Ok(())
+            }
             Err(err) => Err(Error::CommunicationError(err.to_string())),
         }
     }
@@ -229,6 +573,23 @@ impl UsbDevice for Device {
         self.reset().await
     }
 
+    async fn is_open(&self) -> bool {
+        if !self.open.load(Ordering::Acquire) {
+            return false;
+        }
+
+        // The handle stops working once the device is unplugged, which is
+        // when the operating system stops listing it
+        let bus_number = self.device_info.device_info.bus_number();
+        let device_address = self.device_info.device_info.device_address();
+        match nusb::list_devices() {
+            Ok(mut devices) => devices.any(|device_info| {
+                device_info.bus_number() == bus_number && device_info.device_address() == device_address
+            }),
+            Err(_) => true,
+        }
+    }
+
     async fn product_id(&self) -> u16 {
         self.device_info.product_id().await
     }
@@ -245,12 +606,179 @@ impl UsbDevice for Device {
         self.device_info.subclass().await
     }
 
+    async fn protocol(&self) -> u8 {
+        self.device_info.protocol().await
+    }
+
     async fn manufacturer_string(&self) -> Option<String> {
-        self.device_info.manufacturer_string().await
+        match self.device_info.manufacturer_string().await {
+            Some(manufacturer) => Some(manufacturer),
+            None => self.manufacturer_string_in(None).await.ok().flatten(),
+        }
     }
 
     async fn product_string(&self) -> Option<String> {
-        self.device_info.product_string().await
+        match self.device_info.product_string().await {
+            Some(product) => Some(product),
+            None => self.product_string_in(None).await.ok().flatten(),
+        }
+    }
+
+    async fn serial_number_string(&self) -> Option<String> {
+        match self.device_info.serial_number_string().await {
+            Some(serial) => Some(serial),
+            None => self.serial_number_string_in(None).await.ok().flatten(),
+        }
+    }
+
+    async fn device_version(&self) -> Version {
+        self.device_info.device_version().await
+    }
+
+    async fn usb_version(&self) -> Option<Version> {
+        let descriptor = self.device_descriptor_bytes().await.ok()?;
+        let bcd = u16::from_le_bytes([descriptor[2], descriptor[3]]);
+
+        Some(Version::from_bcd(bcd))
+    }
+
+    async fn speed(&self) -> Option<Speed> {
+        self.device_info.speed().await
+    }
+
+    async fn bus_number(&self) -> Option<u8> {
+        self.device_info.bus_number().await
+    }
+
+    async fn device_address(&self) -> Option<u8> {
+        self.device_info.device_address().await
+    }
+
+    async fn port_chain(&self) -> Option<Vec<u8>> {
+        self.device_info.port_chain().await
+    }
+
+    async fn platform_path(&self) -> Option<String> {
+        self.device_info.platform_path().await
+    }
+}
+
+impl Device {
+    pub(crate) fn key(&self) -> DeviceKey {
+        self.device_info.key()
+    }
+
+    pub(crate) fn connected(&self) -> &Arc<AtomicBool> {
+        &self.connected
+    }
+
+    // Tell an interface which is held by something else apart from other
+    // failures, since it may become available later
+    async fn claim_error(&self, number: u8, err: std::io::Error) -> Error {
+        if err.kind() == std::io::ErrorKind::ResourceBusy {
+            return Error::Busy {
+                driver: self.kernel_driver(number).await.ok().flatten(),
+            };
+        }
+
+        Error::CommunicationError(err.to_string())
+    }
+
+    pub(crate) fn descriptor_cache(&self) -> &DescriptorCache {
+        &self.descriptor_cache
+    }
+
+    pub(crate) async fn configuration_descriptors(&self) -> Result<Vec<Vec<u8>>, Error> {
+        Ok(self
+            .device
+            .configurations()
+            .map(|configuration| configuration.descriptors().as_bytes().to_vec())
+            .collect())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "android"))]
+    pub(crate) async fn control_in(&self, data: ControlIn) -> Result<Vec<u8>, Error> {
+        match self.device.control_in(data.into()).await.into_result() {
+            Ok(data) => Ok(data),
+            Err(err) => Err(disconnect_error(&self.connected, err.into())),
+        }
+    }
+
+    // Windows only allows control transfers through a claimed interface
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "android")))]
+    pub(crate) async fn control_in(&self, _data: ControlIn) -> Result<Vec<u8>, Error> {
+        Err(Error::CommunicationError(
+            "control transfers to the device need a claimed interface on this platform".to_string(),
+        ))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "android"))]
+    pub(crate) async fn control_out(&self, data: ControlOut<'_>) -> Result<usize, Error> {
+        match self.device.control_out(data.into()).await.into_result() {
+            Ok(bytes) => Ok(bytes.actual_length()),
+            Err(err) => Err(disconnect_error(&self.connected, err.into())),
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "android")))]
+    pub(crate) async fn control_out(&self, _data: ControlOut<'_>) -> Result<usize, Error> {
+        Err(Error::CommunicationError(
+            "control transfers to the device need a claimed interface on this platform".to_string(),
+        ))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "android"))]
+    pub(crate) async fn get_descriptor(
+        &self,
+        descriptor_type: u8,
+        index: u8,
+        language: u16,
+        length: u16,
+    ) -> Result<Vec<u8>, Error> {
+        const REQUEST_GET_DESCRIPTOR: u8 = 0x06;
+
+        let request = self.control_in(ControlIn {
+            control_type: ControlType::Standard,
+            recipient: Recipient::Device,
+            request: REQUEST_GET_DESCRIPTOR,
+            value: ((descriptor_type as u16) << 8) | index as u16,
+            index: language,
+            length,
+        });
+
+        timer::bounded(Some(DESCRIPTOR_TIMEOUT), request).await
+    }
+
+    // Windows only allows control transfers through a claimed interface, but
+    // nusb can ask the hub for the descriptors instead. That blocks, so it's
+    // done on a thread of its own rather than on the executor
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "android")))]
+    pub(crate) async fn get_descriptor(
+        &self,
+        descriptor_type: u8,
+        index: u8,
+        language: u16,
+        length: u16,
+    ) -> Result<Vec<u8>, Error> {
+        let (sender, receiver) = futures_channel::oneshot::channel();
+
+        let device = self.device.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(device.get_descriptor(descriptor_type, index, language, DESCRIPTOR_TIMEOUT));
+        });
+
+        match receiver.await {
+            Ok(Ok(mut descriptor)) => {
+                descriptor.truncate(length as usize);
+                Ok(descriptor)
+            }
+            // A request the device doesn't support is stalled
+            Ok(Err(err)) if err.kind() == std::io::ErrorKind::BrokenPipe => Err(Error::Stall),
+            Ok(Err(err)) => Err(Error::CommunicationError(err.to_string())),
+            Err(_) => Err(Error::CommunicationError(
+                "the descriptor request stopped without an answer".to_string(),
+            )),
+        }
     }
 }
 
@@ -260,49 +788,298 @@ impl Drop for Device {
     }
 }
 
-impl<'a> UsbInterface<'a> for Interface {
-    async fn control_in(&self, data: ControlIn) -> Result<Vec<u8>, Error> {
-        let result = match self.interface.control_in(data.into()).await.into_result() {
+impl Interface {
+    pub(crate) fn descriptor_cache(&self) -> &DescriptorCache {
+        &self.descriptor_cache
+    }
+
+    pub(crate) async fn get_descriptor(
+        &self,
+        descriptor_type: u8,
+        index: u8,
+        language: u16,
+        length: u16,
+    ) -> Result<Vec<u8>, Error> {
+        const REQUEST_GET_DESCRIPTOR: u8 = 0x06;
+
+        self.control_in(ControlIn {
+            control_type: ControlType::Standard,
+            recipient: Recipient::Device,
+            request: REQUEST_GET_DESCRIPTOR,
+            value: ((descriptor_type as u16) << 8) | index as u16,
+            index: language,
+            length,
+        })
+        .await
+    }
+
+    async fn control_in_with(&self, data: ControlIn, timeout: Option<Duration>) -> Result<Vec<u8>, Error> {
+        let transfer = self.interface.control_in(data.into());
+
+        let result = match timer::bounded(timeout, self.cancellation.run(transfer)).await?.into_result() {
             Ok(res) => res,
-            Err(_) => return Err(Error::TransferError),
+            Err(err) => return Err(disconnect_error(&self.connected, err.into())),
         };
 
         Ok(result)
     }
 
-    async fn control_out(&self, data: ControlOut<'a>) -> Result<usize, Error> {
-        match self.interface.control_out(data.into()).await.into_result() {
+    async fn control_out_with(&self, data: ControlOut<'_>, timeout: Option<Duration>) -> Result<usize, Error> {
+        let transfer = self.interface.control_out(data.into());
+
+        match timer::bounded(timeout, self.cancellation.run(transfer)).await?.into_result() {
             Ok(bytes) => Ok(bytes.actual_length()),
-            Err(_) => Err(Error::TransferError),
+            Err(err) => Err(disconnect_error(&self.connected, err.into())),
         }
     }
 
-    async fn bulk_in(&self, endpoint: u8, length: usize) -> Result<Vec<u8>, Error> {
-        let request_buffer = nusb::transfer::RequestBuffer::new(length);
+    async fn bulk_in_with(&self, endpoint: u8, length: usize, timeout: Option<Duration>) -> Result<TransferResult, Error> {
+        self.bulk_in_reuse(endpoint, length, Vec::new(), timeout).await
+    }
 
-        match self
-            .interface
-            .bulk_in(endpoint, request_buffer)
+    async fn bulk_in_reuse(&self, endpoint: u8, length: usize, buffer: Vec<u8>, timeout: Option<Duration>) -> Result<TransferResult, Error> {
+        let request_buffer = nusb::transfer::RequestBuffer::reuse(buffer, length);
+        let transfer = self.interface.bulk_in(endpoint, request_buffer);
+
+        let data = match timer::bounded(timeout, self.cancellation.run(transfer)).await?.into_result() {
+            Ok(res) => res,
+            Err(err) => return Err(disconnect_error(&self.connected, err.into())),
+        };
+
+        Ok(TransferResult {
+            short_packet: data.len() < length,
+            data,
+            status: TransferStatus::Completed,
+        })
+    }
+
+    pub(crate) async fn bulk_in_until(&self, endpoint: u8, length: usize, deadline: &Deadline) -> Result<Vec<u8>, Error> {
+        let result = self
+            .retry
+            .run_until(deadline, |remaining| self.bulk_in_with(endpoint, length, Some(remaining)))
+            .await?;
+
+        Ok(result.data)
+    }
+
+    pub(crate) async fn bulk_out_until(&self, endpoint: u8, data: &[u8], deadline: &Deadline) -> Result<usize, Error> {
+        self.retry
+            .run_out_until(deadline, |remaining| self.bulk_out_with(endpoint, data.to_vec(), Some(remaining)))
             .await
-            .into_result()
-        {
-            Ok(res) => Ok(res),
-            Err(_) => Err(Error::TransferError),
+    }
+
+    async fn bulk_out_with(&self, endpoint: u8, data: Vec<u8>, timeout: Option<Duration>) -> Result<usize, Error> {
+        // Submitted through a queue, so a transfer which is stopped early can
+        // be cancelled and waited for to find out how much of it was sent
+        let mut queue = self.interface.bulk_out_queue(endpoint);
+        queue.submit(data);
+
+        let completion = match timer::bounded(timeout, self.cancellation.run(queue.next_complete())).await {
+            Ok(completion) => completion,
+            Err(err @ (Error::Timeout | Error::Cancelled)) => {
+                queue.cancel_all();
+                let completion = queue.next_complete().await;
+
+                // It may have finished before it could be cancelled
+                return match completion.status {
+                    Ok(()) => Ok(completion.data.actual_length()),
+                    Err(_) => Err(Error::partial(completion.data.actual_length(), err)),
+                };
+            }
+            Err(err) => return Err(err),
+        };
+
+        match completion.status {
+            Ok(()) => Ok(completion.data.actual_length()),
+            Err(err) => {
+                let err = disconnect_error(&self.connected, err.into());
+                Err(Error::partial(completion.data.actual_length(), err))
+            }
+        }
+    }
+}
+
+impl<'a> UsbInterface<'a> for Interface {
+    async fn control_in(&self, data: ControlIn) -> Result<Vec<u8>, Error> {
+        self.retry.run(|| self.control_in_with(data, self.default_timeout)).await
+    }
+
+    async fn control_out(&self, data: ControlOut<'a>) -> Result<usize, Error> {
+        self.retry.run_out(|| self.control_out_with(data, self.default_timeout)).await
+    }
+
+    async fn bulk_in(&self, endpoint: u8, length: usize) -> Result<Vec<u8>, Error> {
+        Ok(self.retry.run(|| self.bulk_in_with(endpoint, length, self.default_timeout)).await?.data)
+    }
+
+    async fn bulk_in_into(&self, endpoint: u8, length: usize, buf: &mut Vec<u8>) -> Result<usize, Error> {
+        // Every retry needs its own buffer
+        if self.retry.max_attempts > 1 {
+            buf.clear();
+            buf.extend_from_slice(&self.bulk_in(endpoint, length).await?);
+            return Ok(buf.len());
         }
+
+        let buffer = std::mem::take(buf);
+        *buf = self.bulk_in_reuse(endpoint, length, buffer, self.default_timeout).await?.data;
+
+        Ok(buf.len())
+    }
+
+    async fn bulk_in_result(&self, endpoint: u8, length: usize) -> Result<TransferResult, Error> {
+        self.retry.run(|| self.bulk_in_with(endpoint, length, self.default_timeout)).await
     }
 
     async fn bulk_out(&self, endpoint: u8, data: &[u8]) -> Result<usize, Error> {
-        match self
+        self.retry.run_out(|| self.bulk_out_with(endpoint, data.to_vec(), self.default_timeout)).await
+    }
+
+    async fn bulk_out_owned(&self, endpoint: u8, data: Vec<u8>) -> Result<usize, Error> {
+        // Every retry needs its own copy of the data
+        if self.retry.max_attempts > 1 {
+            return self.bulk_out(endpoint, &data).await;
+        }
+
+        self.bulk_out_with(endpoint, data, self.default_timeout).await
+    }
+
+    async fn control_in_timeout(&self, data: ControlIn, timeout: Duration) -> Result<Vec<u8>, Error> {
+        self.retry.run(|| self.control_in_with(data, Some(timeout))).await
+    }
+
+    async fn control_out_timeout(&self, data: ControlOut<'a>, timeout: Duration) -> Result<usize, Error> {
+        self.retry.run_out(|| self.control_out_with(data, Some(timeout))).await
+    }
+
+    async fn bulk_in_timeout(&self, endpoint: u8, length: usize, timeout: Duration) -> Result<Vec<u8>, Error> {
+        Ok(self.retry.run(|| self.bulk_in_with(endpoint, length, Some(timeout))).await?.data)
+    }
+
+    async fn bulk_out_timeout(&self, endpoint: u8, data: &[u8], timeout: Duration) -> Result<usize, Error> {
+        self.retry.run_out(|| self.bulk_out_with(endpoint, data.to_vec(), Some(timeout))).await
+    }
+
+    fn set_default_timeout(&mut self, timeout: Option<Duration>) {
+        self.default_timeout = timeout;
+    }
+
+    fn default_timeout(&self) -> Option<Duration> {
+        self.default_timeout
+    }
+
+    fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    fn set_in_flight(&mut self, endpoint: u8, depth: usize) {
+        self.in_flight.insert(endpoint, depth.max(1));
+    }
+
+    fn in_flight(&self, endpoint: u8) -> usize {
+        self.in_flight.get(&endpoint).copied().unwrap_or(1)
+    }
+
+    async fn clear_halt(&self, endpoint: u8) -> Result<(), Error> {
+        match self.interface.clear_halt(endpoint) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::CommunicationError(err.to_string())),
+        }
+    }
+
+    fn interface_number(&self) -> u8 {
+        self.number
+    }
+
+    fn alt_settings(&self) -> Vec<AltSettingInfo> {
+        self.interface
+            .descriptors()
+            .map(|setting| AltSettingInfo {
+                alt_setting: setting.alternate_setting(),
+                class: setting.class(),
+                subclass: setting.subclass(),
+                protocol: setting.protocol(),
+                num_endpoints: setting.num_endpoints(),
+            })
+            .collect()
+    }
+
+    fn endpoints(&self) -> Vec<EndpointInfo> {
+        let alt_setting = self.alt_setting.load(Ordering::Acquire);
+
+        let Some(setting) = self
             .interface
-            .bulk_out(endpoint, data.to_vec())
-            .await
-            .into_result()
-        {
-            Ok(len) => Ok(len.actual_length()),
-            Err(_) => Err(Error::TransferError),
+            .descriptors()
+            .find(|setting| setting.alternate_setting() == alt_setting)
+        else {
+            return Vec::new();
+        };
+
+        setting
+            .endpoints()
+            .map(|endpoint| {
+                let transfer_type = match endpoint.transfer_type() {
+                    nusb::transfer::EndpointType::Control => TransferType::Control,
+                    nusb::transfer::EndpointType::Isochronous => TransferType::Isochronous,
+                    nusb::transfer::EndpointType::Bulk => TransferType::Bulk,
+                    nusb::transfer::EndpointType::Interrupt => TransferType::Interrupt,
+                };
+
+                EndpointInfo {
+                    address: endpoint.address(),
+                    direction: match endpoint.direction() {
+                        nusb::transfer::Direction::In => Direction::In,
+                        nusb::transfer::Direction::Out => Direction::Out,
+                    },
+                    transfer_type,
+                    max_packet_size: endpoint.max_packet_size(),
+                    interval: Some(endpoint.interval()),
+                    interval_micros: descriptor::interval_micros(
+                        transfer_type,
+                        endpoint.interval(),
+                        self.speed,
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    async fn interface_string(&self) -> Result<Option<String>, Error> {
+        let alt_setting = self.alt_setting.load(Ordering::Acquire);
+
+        let index = self
+            .interface
+            .descriptors()
+            .find(|setting| setting.alternate_setting() == alt_setting)
+            .and_then(|setting| setting.string_index());
+
+        match index {
+            Some(index) => self.string_descriptor(index).await,
+            None => Ok(None),
         }
     }
 
+    async fn set_alt_setting(&self, alt_setting: u8) -> Result<(), Error> {
+        match self.interface.set_alt_setting(alt_setting) {
+            Ok(_) => {
+                self.alt_setting.store(alt_setting, Ordering::Release);
+                Ok(())
+            }
+            Err(err) => Err(Error::CommunicationError(err.to_string())),
+        }
+    }
+
+    fn cancel_all(&self) {
+        self.cancellation.cancel_all()
+    }
+
+    fn can_abort_transfers(&self) -> bool {
+        true
+    }
+
     /*
     async fn interrupt_in(&self, endpoint: u8, length: usize) -> Result<Vec<u8>, UsbError> {
         let buf = Vec::new();
@@ -323,6 +1100,17 @@ impl<'a> UsbInterface<'a> for Interface {
     */
 }
 
+impl From<nusb::transfer::TransferError> for Error {
+    fn from(val: nusb::transfer::TransferError) -> Self {
+        match val {
+            nusb::transfer::TransferError::Disconnected => Error::Disconnected,
+            nusb::transfer::TransferError::Cancelled => Error::Cancelled,
+            nusb::transfer::TransferError::Stall => Error::Stall,
+            _ => Error::TransferError,
+        }
+    }
+}
+
 impl From<ControlIn> for nusb::transfer::ControlIn {
     fn from(val: ControlIn) -> Self {
         nusb::transfer::ControlIn {
@@ -369,3 +1157,39 @@ impl From<Recipient> for nusb::transfer::Recipient {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_match_the_target() {
+        // Listing every field makes this fail to build when one is added
+        let Capabilities {
+            enumerate_without_prompt,
+            detach_kernel_driver,
+            abort_transfers,
+            speed,
+            usb_version,
+            port_chain,
+            device_control_transfers,
+            hotplug,
+            interrupt_transfers,
+            isochronous_transfers,
+        } = capabilities();
+
+        assert!(enumerate_without_prompt);
+        assert_eq!(detach_kernel_driver, cfg!(target_os = "linux"));
+        assert!(abort_transfers);
+        assert_eq!(speed, cfg!(any(target_os = "linux", target_os = "macos", target_os = "windows")));
+        assert_eq!(usb_version, cfg!(target_os = "linux"));
+        assert_eq!(port_chain, cfg!(any(target_os = "linux", target_os = "macos")));
+        assert_eq!(
+            device_control_transfers,
+            cfg!(any(target_os = "linux", target_os = "macos", target_os = "android")),
+        );
+        assert!(hotplug);
+        assert!(!interrupt_transfers);
+        assert!(!isochronous_transfers);
+    }
+}
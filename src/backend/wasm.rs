@@ -1,17 +1,37 @@
 //#![cfg_attr(debug_assertions, allow(dead_code, unused_imports))]
+use std::cell::RefCell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_channel::mpsc::UnboundedReceiver;
+use futures_channel::oneshot;
+use futures_core::Stream;
 use wasm_bindgen::prelude::*;
 
-use js_sys::{Array, Object, Promise, Uint8Array};
+use js_sys::{Array, DataView, Object, Promise, Uint8Array};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-    UsbControlTransferParameters, UsbDevice as WasmUsbDevice, UsbDeviceRequestOptions,
-    UsbInTransferResult, UsbOutTransferResult, UsbRecipient, UsbRequestType,
+    Usb, UsbAlternateInterface, UsbConnectionEvent, UsbControlTransferParameters, UsbDevice as WasmUsbDevice,
+    UsbDeviceRequestOptions, UsbDirection, UsbEndpoint, UsbEndpointType, UsbInTransferResult,
+    UsbOutTransferResult, UsbRecipient, UsbRequestType, UsbTransferStatus,
 };
 
 // Crate stuff
+use crate::cancel::Cancellation;
+use crate::descriptor::DescriptorCache;
+use crate::filter;
+use crate::timer::{self, Deadline};
 use crate::usb::{
-    ControlIn, ControlOut, ControlType, UsbDeviceInfo, UsbDevice, UsbInterface, Recipient, Error,
+    Capabilities, ControlIn, ControlOut, ControlType, UsbDeviceInfo, UsbDevice, UsbInterface, Recipient, Error,
+    AltSettingInfo, DeviceInfoSnapshot, Direction, EndpointInfo, GetDeviceOptions, InterfaceFilterInfo, PermissionState,
+    RetryPolicy, Speed,
+    TransferResult, TransferStatus, TransferType, Version,
 };
+use crate::watch::disconnect_error;
 
 #[wasm_bindgen]
 #[derive(Debug)]
@@ -23,26 +43,45 @@ pub struct DeviceInfo {
 #[derive(Debug)]
 pub struct Device {
     device: WasmUsbDevice,
+    // Cleared once the device is known to have been unplugged
+    connected: Arc<AtomicBool>,
+    descriptor_cache: DescriptorCache,
 }
 
 #[wasm_bindgen]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Interface {
     device: WasmUsbDevice,
-    _number: u8,
+    number: u8,
+    cancellation: Cancellation,
+    default_timeout: Option<Duration>,
+    retry: RetryPolicy,
+    connected: Arc<AtomicBool>,
 }
 
 #[wasm_bindgen]
-#[derive(PartialEq, Clone, Default)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct DeviceFilter {
     pub vendor_id: Option<u16>,
     pub product_id: Option<u16>,
     pub class: Option<u8>,
     pub subclass: Option<u8>,
     pub protocol: Option<u8>,
+    #[wasm_bindgen(getter_with_clone)]
+    pub serial_number: Option<String>,
+    #[wasm_bindgen(getter_with_clone)]
+    pub manufacturer_string_contains: Option<String>,
+    #[wasm_bindgen(getter_with_clone)]
+    pub product_string_contains: Option<String>,
+    #[wasm_bindgen(skip)]
+    pub device_version_min: Option<Version>,
+    #[wasm_bindgen(skip)]
+    pub device_version_max: Option<Version>,
+    pub exclude: bool,
 }
 
 impl DeviceFilter {
+    #[deprecated(note = "use `DeviceFilter::builder` instead")]
     pub fn new(
         vendor_id: Option<u16>,
         product_id: Option<u16>,
@@ -56,227 +95,500 @@ impl DeviceFilter {
             class,
             subclass,
             protocol,
+            serial_number: None,
+            manufacturer_string_contains: None,
+            product_string_contains: None,
+            device_version_min: None,
+            device_version_max: None,
+            exclude: false,
         }
     }
 }
 
 #[wasm_bindgen]
-pub async fn get_device(device_filter: Vec<DeviceFilter>) -> Result<DeviceInfo, js_sys::Error> {
-    let window = web_sys::window().unwrap();
+pub async fn get_device(device_filter: Vec<DeviceFilter>) -> Result<DeviceInfo, Error> {
+    // WebUSB can't show the chooser without a filter, and no device would
+    // match on native either
+    if !filter::includes_any(&device_filter) {
+        return Err(Error::DeviceNotFound);
+    }
 
-    let navigator = window.navigator();
-    let usb = navigator.usb();
+    let usb = usb()?;
 
-    let device_list: Array = match JsFuture::from(Promise::resolve(&usb.get_devices())).await {
-        Ok(list) => list.into(),
-        Err(_) => Array::new(),
+    // Check if the device is already paired, if so, we don't need to request it again.
+    // Prefer the device matching the most specific filter
+    let paired = list_devices().await.unwrap_or_default();
+    let device_info = match filter::best(&device_filter, paired).await {
+        Some(device_info) => device_info,
+        None => picked_device(usb.request_device(&request_options(&device_filter))).await?,
     };
 
+    if let Err(err) = JsFuture::from(Promise::resolve(&device_info.device.open())).await {
+        return Err(Error::CommunicationError(error_message(&err)));
+    }
+
+    Ok(device_info)
+}
+
+pub async fn get_device_with_options(
+    device_filters: Vec<DeviceFilter>,
+    options: GetDeviceOptions,
+) -> Result<DeviceInfo, Error> {
+    if options.prefer_paired {
+        if let Some(device_info) = filter::best(&device_filters, list_devices().await?).await {
+            return Ok(device_info);
+        }
+    }
+
+    if !options.allow_prompt {
+        return Err(Error::DeviceNotFound);
+    }
+
+    request_device(device_filters).await
+}
+
+pub async fn get_device_with(
+    device_filter: Vec<DeviceFilter>,
+    predicate: impl Fn(&DeviceInfoSnapshot) -> bool,
+) -> Result<DeviceInfo, Error> {
     // Check if the device is already paired, if so, we don't need to request it again
-    for js_device in device_list {
-        let device: WasmUsbDevice = js_device.into();
+    for device_info in list_devices().await? {
+        if filter::accepts(&device_filter, &device_info, &predicate).await {
+            return Ok(device_info);
+        }
+    }
 
-        if device_filter.iter().any(|info| {
-            let mut result = false;
+    // The browser can only pick from the filters, so check the device it
+    // picked too
+    let device_info = request_device(device_filter).await?;
+    if !predicate(&device_info.info().await) {
+        return Err(Error::DeviceNotFound);
+    }
 
-            if info.vendor_id.is_some() {
-                result = info.vendor_id.unwrap() == device.vendor_id();
-            }
+    Ok(device_info)
+}
 
-            if info.product_id.is_some() {
-                result = info.product_id.unwrap() == device.product_id();
-            }
+pub async fn request_device(device_filter: Vec<DeviceFilter>) -> Result<DeviceInfo, Error> {
+    if !filter::includes_any(&device_filter) {
+        return Err(Error::DeviceNotFound);
+    }
 
-            if info.class.is_some() {
-                result = info.class.unwrap() == device.device_class();
-            }
+    let usb = usb()?;
+    let options = request_options(&device_filter);
 
-            if info.subclass.is_some() {
-                result = info.subclass.unwrap() == device.device_subclass();
-            }
+    picked_device(usb.request_device(&options)).await
+}
 
-            if info.protocol.is_some() {
-                result = info.protocol.unwrap() == device.device_protocol();
-            }
+// The device picked in the chooser opened by `requestDevice`
+async fn picked_device(request: Promise) -> Result<DeviceInfo, Error> {
+    match JsFuture::from(request).await {
+        Ok(device) => Ok(DeviceInfo {
+            device: device.into(),
+        }),
+        // The user closed the chooser without picking a device
+        Err(err) if error_name(&err).as_deref() == Some("NotFoundError") => Err(Error::DeviceNotFound),
+        // The chooser can only be shown in response to a user gesture
+        Err(err) if error_name(&err).as_deref() == Some("SecurityError") => Err(Error::PermissionDenied),
+        Err(err) => Err(Error::CommunicationError(error_message(&err))),
+    }
+}
+
+// A request for a device which is made by `trigger`, in a user gesture, and
+// waited for by `device` wherever is convenient. Clones share the request
+#[derive(Debug, Clone)]
+pub(crate) struct PendingRequest {
+    state: Rc<RequestState>,
+}
+
+#[derive(Debug)]
+struct RequestState {
+    device_filters: Vec<DeviceFilter>,
+    sender: RefCell<Option<oneshot::Sender<Result<Promise, Error>>>>,
+    receiver: RefCell<Option<oneshot::Receiver<Result<Promise, Error>>>>,
+}
 
-            result
-        }) {
-            let _open_promise = JsFuture::from(Promise::resolve(&device.open())).await?;
-            return Ok(DeviceInfo { device });
+impl PendingRequest {
+    pub(crate) fn new(device_filters: Vec<DeviceFilter>) -> Self {
+        let (sender, receiver) = oneshot::channel();
+
+        Self {
+            state: Rc::new(RequestState {
+                device_filters,
+                sender: RefCell::new(Some(sender)),
+                receiver: RefCell::new(Some(receiver)),
+            }),
         }
     }
 
-    let arr = Array::new();
-    for filter in device_filter {
-        let js_filter = js_sys::Object::new();
-        if let Some(vid) = filter.vendor_id {
-            js_sys::Reflect::set(
-                &js_filter,
-                &JsValue::from_str("vendorId"),
-                &JsValue::from(vid),
-            )
-            .unwrap();
-        }
-        if let Some(pid) = filter.product_id {
-            js_sys::Reflect::set(
-                &js_filter,
-                &JsValue::from_str("productId"),
-                &JsValue::from(pid),
-            )
-            .unwrap();
-        }
-        if let Some(class) = filter.class {
-            js_sys::Reflect::set(
-                &js_filter,
-                &JsValue::from_str("classCode"),
-                &JsValue::from(class),
-            )
-            .unwrap();
-        }
-        if let Some(subclass) = filter.subclass {
-            js_sys::Reflect::set(
-                &js_filter,
-                &JsValue::from_str("subclassCode"),
-                &JsValue::from(subclass),
-            )
-            .unwrap();
+    pub(crate) fn trigger(&self) {
+        // Only the first call shows the chooser
+        let Some(sender) = self.state.sender.borrow_mut().take() else {
+            return;
+        };
+
+        // `requestDevice` has to be called before this returns to be part of
+        // the user gesture, so only its promise is passed on
+        let device_filters = &self.state.device_filters;
+        let request = if filter::includes_any(device_filters) {
+            usb().map(|usb| usb.request_device(&request_options(device_filters)))
+        } else {
+            Err(Error::DeviceNotFound)
+        };
+        let _ = sender.send(request);
+    }
+
+    pub(crate) async fn device(&self) -> Result<DeviceInfo, Error> {
+        // Only one clone can wait for the picked device
+        let Some(receiver) = self.state.receiver.borrow_mut().take() else {
+            return Err(Error::DeviceNotFound);
+        };
+
+        match receiver.await {
+            Ok(request) => picked_device(request?).await,
+            Err(_) => Err(Error::DeviceNotFound),
         }
-        if let Some(pro) = filter.protocol {
-            js_sys::Reflect::set(
-                &js_filter,
-                &JsValue::from_str("protocolCode"),
-                &JsValue::from(pro),
-            )
-            .unwrap();
+    }
+}
+
+// The options for `requestDevice`, which shows the user the devices matching
+// any of `device_filters` to pick from, except ones matching a filter which
+// excludes devices
+fn request_options(device_filters: &[DeviceFilter]) -> UsbDeviceRequestOptions {
+    let filters = Array::new();
+    let exclusion_filters = Array::new();
+    for filter in device_filters {
+        if filter.exclude {
+            exclusion_filters.push(&js_filter(filter));
+        } else {
+            filters.push(&js_filter(filter));
         }
-        arr.push(&js_filter);
     }
 
-    let filters = JsValue::from(&arr);
-    let filters2 = UsbDeviceRequestOptions::new(&filters);
+    let options = UsbDeviceRequestOptions::new(&JsValue::from(&filters));
+    if exclusion_filters.length() > 0 {
+        js_sys::Reflect::set(
+            &options,
+            &JsValue::from_str("exclusionFilters"),
+            &JsValue::from(&exclusion_filters),
+        )
+        .unwrap();
+    }
+
+    options
+}
+
+// A `USBDeviceFilter` with the fields of `filter`. The browser can't match
+// strings, so they are only checked against devices the page already has
+// access to
+fn js_filter(filter: &DeviceFilter) -> Object {
+    let js_filter = js_sys::Object::new();
+    if let Some(vid) = filter.vendor_id {
+        js_sys::Reflect::set(
+            &js_filter,
+            &JsValue::from_str("vendorId"),
+            &JsValue::from(vid),
+        )
+        .unwrap();
+    }
+    if let Some(pid) = filter.product_id {
+        js_sys::Reflect::set(
+            &js_filter,
+            &JsValue::from_str("productId"),
+            &JsValue::from(pid),
+        )
+        .unwrap();
+    }
+    if let Some(class) = filter.class {
+        js_sys::Reflect::set(
+            &js_filter,
+            &JsValue::from_str("classCode"),
+            &JsValue::from(class),
+        )
+        .unwrap();
+    }
+    if let Some(subclass) = filter.subclass {
+        js_sys::Reflect::set(
+            &js_filter,
+            &JsValue::from_str("subclassCode"),
+            &JsValue::from(subclass),
+        )
+        .unwrap();
+    }
+    if let Some(pro) = filter.protocol {
+        js_sys::Reflect::set(
+            &js_filter,
+            &JsValue::from_str("protocolCode"),
+            &JsValue::from(pro),
+        )
+        .unwrap();
+    }
+    if let Some(serial) = &filter.serial_number {
+        js_sys::Reflect::set(
+            &js_filter,
+            &JsValue::from_str("serialNumber"),
+            &JsValue::from(serial.as_str()),
+        )
+        .unwrap();
+    }
 
-    let device: WasmUsbDevice = JsFuture::from(Promise::resolve(&usb.request_device(&filters2)))
-        .await?
-        .into();
+    js_filter
+}
 
-    let _open_promise = JsFuture::from(Promise::resolve(&device.open())).await?;
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        enumerate_without_prompt: false,
+        detach_kernel_driver: false,
+        abort_transfers: false,
+        speed: false,
+        usb_version: true,
+        port_chain: false,
+        device_control_transfers: true,
+        hotplug: true,
+        interrupt_transfers: false,
+        isochronous_transfers: false,
+    }
+}
 
-    Ok(DeviceInfo { device })
+pub fn is_supported() -> bool {
+    usb().is_ok()
 }
 
-#[wasm_bindgen]
-pub async fn get_device_list(device_filter: Vec<DeviceFilter>) -> Result<Vec<DeviceInfo>, js_sys::Error> {
-    let window = web_sys::window().unwrap();
+pub async fn permission_state() -> Result<PermissionState, Error> {
+    usb()?;
+
+    // Not every browser has the Permissions API, or knows the `usb`
+    // permission, which it rejects the query for
+    let Some(window) = web_sys::window() else {
+        return Ok(PermissionState::Unsupported);
+    };
+    let permissions = match js_sys::Reflect::get(&window.navigator(), &JsValue::from_str("permissions")) {
+        Ok(permissions) if permissions.is_object() => permissions,
+        _ => return Ok(PermissionState::Unsupported),
+    };
+    let Ok(query) = js_sys::Reflect::get(&permissions, &JsValue::from_str("query")) else {
+        return Ok(PermissionState::Unsupported);
+    };
+    let Some(query) = query.dyn_ref::<js_sys::Function>() else {
+        return Ok(PermissionState::Unsupported);
+    };
+
+    let descriptor = Object::new();
+    let _ = js_sys::Reflect::set(&descriptor, &JsValue::from_str("name"), &JsValue::from_str("usb"));
+
+    let status = match query.call1(&permissions, &descriptor) {
+        Ok(promise) => JsFuture::from(Promise::resolve(&promise)).await,
+        Err(err) => Err(err),
+    };
+    let Ok(status) = status else {
+        return Ok(PermissionState::Unsupported);
+    };
+
+    let state = js_sys::Reflect::get(&status, &JsValue::from_str("state"))
+        .ok()
+        .and_then(|state| state.as_string());
+    match state.as_deref() {
+        Some("granted") => Ok(PermissionState::Granted),
+        Some("prompt") => Ok(PermissionState::Prompt),
+        Some("denied") => Ok(PermissionState::Denied),
+        _ => Ok(PermissionState::Unsupported),
+    }
+}
+
+thread_local! {
+    // The `USB` object from `set_usb_context`, used instead of `navigator.usb`
+    static USB_CONTEXT: RefCell<Option<Usb>> = const { RefCell::new(None) };
+}
+
+pub fn set_usb_context(usb: Option<JsValue>) {
+    USB_CONTEXT.with(|context| *context.borrow_mut() = usb.map(JsCast::unchecked_into));
+}
+
+// `navigator.usb` is missing in browsers without WebUSB, and outside of
+// secure contexts
+fn usb() -> Result<Usb, Error> {
+    if let Some(usb) = USB_CONTEXT.with(|context| context.borrow().clone()) {
+        return Ok(usb);
+    }
+
+    let Some(window) = web_sys::window() else {
+        return Err(Error::NotSupported);
+    };
+
+    if !window.is_secure_context() {
+        return Err(Error::NotSupported);
+    }
 
-    let navigator = window.navigator();
-    let usb = navigator.usb();
+    match js_sys::Reflect::get(&window.navigator(), &JsValue::from_str("usb")) {
+        Ok(usb) if !usb.is_undefined() && !usb.is_null() => Ok(usb.unchecked_into()),
+        _ => Err(Error::NotSupported),
+    }
+}
+
+// Every device the page has been given access to, without prompting the
+// user or opening them
+pub(crate) async fn list_devices() -> Result<Vec<DeviceInfo>, Error> {
+    let usb = usb()?;
 
     let device_list: Array = match JsFuture::from(Promise::resolve(&usb.get_devices())).await {
         Ok(list) => list.into(),
-        Err(_) => Array::new(),
+        Err(err) => return Err(Error::CommunicationError(error_message(&err))),
     };
 
     let mut devices = Vec::new();
-    // Check if the device is already paired, if so, we don't need to request it again
     for js_device in device_list {
         let device: WasmUsbDevice = js_device.into();
+        if !is_listed(&devices, &device) {
+            devices.push(DeviceInfo { device });
+        }
+    }
 
-        if device_filter.iter().any(|info| {
-            let mut result = false;
+    Ok(devices)
+}
 
-            if info.vendor_id.is_some() {
-                result = info.vendor_id.unwrap() == device.vendor_id();
-            }
+pub async fn get_device_list(
+    device_filter: Vec<DeviceFilter>,
+) -> Result<Vec<DeviceInfo>, Error> {
+    // Only devices the page already has access to are listed, as asking the
+    // user for more needs a user gesture and shows a chooser
+    let devices = filter::get_paired_devices(device_filter).await?;
+    if devices.is_empty() {
+        return Err(Error::DeviceNotFound);
+    }
 
-            if info.product_id.is_some() {
-                result = info.product_id.unwrap() == device.product_id();
-            }
+    Ok(devices)
+}
 
-            if info.class.is_some() {
-                result = info.class.unwrap() == device.device_class();
-            }
+pub async fn get_device_list_with(
+    device_filter: Vec<DeviceFilter>,
+    predicate: impl Fn(&DeviceInfoSnapshot) -> bool,
+) -> Result<Vec<DeviceInfo>, Error> {
+    // As with `get_device_list`, only devices the page already has access to
+    // are checked
+    let mut devices = Vec::new();
+    for device_info in list_devices().await? {
+        if filter::accepts(&device_filter, &device_info, &predicate).await {
+            devices.push(device_info);
+        }
+    }
 
-            if info.subclass.is_some() {
-                result = info.subclass.unwrap() == device.device_subclass();
-            }
+    if devices.is_empty() {
+        return Err(Error::DeviceNotFound);
+    }
 
-            if info.protocol.is_some() {
-                result = info.protocol.unwrap() == device.device_protocol();
-            }
+    Ok(devices)
+}
 
-            result
-        }) {
-            let _open_promise = JsFuture::from(Promise::resolve(&device.open())).await?;
-            devices.push(DeviceInfo { device });
-        }
-    }
+// Whether `device` is already in `devices`. The browser gives out the same
+// object for the same device
+fn is_listed(devices: &[DeviceInfo], device: &WasmUsbDevice) -> bool {
+    devices
+        .iter()
+        .any(|device_info| Object::is(&device_info.device, device))
+}
 
-    let arr = Array::new();
-    for filter in device_filter {
-        let js_filter = js_sys::Object::new();
-        if let Some(vid) = filter.vendor_id {
-            js_sys::Reflect::set(
-                &js_filter,
-                &JsValue::from_str("vendorId"),
-                &JsValue::from(vid),
-            )
-            .unwrap();
-        }
-        if let Some(pid) = filter.product_id {
-            js_sys::Reflect::set(
-                &js_filter,
-                &JsValue::from_str("productId"),
-                &JsValue::from(pid),
-            )
-            .unwrap();
-        }
-        if let Some(class) = filter.class {
-            js_sys::Reflect::set(
-                &js_filter,
-                &JsValue::from_str("classCode"),
-                &JsValue::from(class),
-            )
-            .unwrap();
-        }
-        if let Some(subclass) = filter.subclass {
-            js_sys::Reflect::set(
-                &js_filter,
-                &JsValue::from_str("subclassCode"),
-                &JsValue::from(subclass),
-            )
-            .unwrap();
-        }
-        if let Some(pro) = filter.protocol {
-            js_sys::Reflect::set(
-                &js_filter,
-                &JsValue::from_str("protocolCode"),
-                &JsValue::from(pro),
-            )
-            .unwrap();
-        }
-        arr.push(&js_filter);
+// Identifies a connected device in hotplug events. The browser hands out the
+// same object for a device until it is disconnected
+#[derive(Debug)]
+pub(crate) struct DeviceKey(WasmUsbDevice);
+
+impl PartialEq for DeviceKey {
+    fn eq(&self, other: &Self) -> bool {
+        Object::is(&self.0, &other.0)
     }
+}
+
+pub(crate) enum HotplugEvent {
+    Connected(DeviceInfo),
+    Disconnected(DeviceKey),
+}
+
+// Devices being connected and disconnected, from the `connect` and
+// `disconnect` events on `navigator.usb`. The browser only fires these for
+// devices the page has access to. The listeners are removed when this is
+// dropped
+pub(crate) struct Hotplug {
+    usb: Usb,
+    events: UnboundedReceiver<HotplugEvent>,
+    on_connect: Closure<dyn FnMut(UsbConnectionEvent)>,
+    on_disconnect: Closure<dyn FnMut(UsbConnectionEvent)>,
+}
+
+impl Hotplug {
+    pub(crate) fn new() -> Result<Self, Error> {
+        let usb = usb()?;
+        let (sender, events) = futures_channel::mpsc::unbounded();
+
+        // Sending only fails once the receiver is gone, and the listeners are
+        // removed along with it
+        let connected = sender.clone();
+        let on_connect = Closure::<dyn FnMut(UsbConnectionEvent)>::new(move |event: UsbConnectionEvent| {
+            let device_info = DeviceInfo { device: event.device() };
+            let _ = connected.unbounded_send(HotplugEvent::Connected(device_info));
+        });
+        let on_disconnect = Closure::<dyn FnMut(UsbConnectionEvent)>::new(move |event: UsbConnectionEvent| {
+            let _ = sender.unbounded_send(HotplugEvent::Disconnected(DeviceKey(event.device())));
+        });
+
+        // Removing a listener which was never added does nothing, so dropping
+        // this part way through is fine
+        let hotplug = Self {
+            usb,
+            events,
+            on_connect,
+            on_disconnect,
+        };
 
-    let filters = JsValue::from(&arr);
-    let filters2 = UsbDeviceRequestOptions::new(&filters);
+        hotplug
+            .usb
+            .add_event_listener_with_callback("connect", hotplug.on_connect.as_ref().unchecked_ref())
+            .map_err(|err| Error::CommunicationError(error_message(&err)))?;
+        hotplug
+            .usb
+            .add_event_listener_with_callback("disconnect", hotplug.on_disconnect.as_ref().unchecked_ref())
+            .map_err(|err| Error::CommunicationError(error_message(&err)))?;
 
-    let device: WasmUsbDevice = JsFuture::from(Promise::resolve(&usb.request_device(&filters2)))
-        .await?
-        .into();
+        Ok(hotplug)
+    }
 
-    let _open_promise = JsFuture::from(Promise::resolve(&device.open())).await?;
+    pub(crate) fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<HotplugEvent>> {
+        Pin::new(&mut self.events).poll_next(cx)
+    }
+}
 
-    devices.push(DeviceInfo { device });
+impl Drop for Hotplug {
+    fn drop(&mut self) {
+        let _ = self
+            .usb
+            .remove_event_listener_with_callback("connect", self.on_connect.as_ref().unchecked_ref());
+        let _ = self
+            .usb
+            .remove_event_listener_with_callback("disconnect", self.on_disconnect.as_ref().unchecked_ref());
+    }
+}
 
-    return Ok(devices);
+impl DeviceInfo {
+    pub(crate) fn key(&self) -> DeviceKey {
+        DeviceKey(self.device.clone())
+    }
 }
 
 impl UsbDeviceInfo for DeviceInfo {
     type Device = Device;
 
     async fn open(self) -> Result<Self::Device, Error> {
+        // Devices from `get_device` are already open, but ones which were
+        // only listed aren't
+        if !self.device.opened() {
+            let result = JsFuture::from(Promise::resolve(&self.device.open())).await;
+            if let Err(err) = result {
+                return Err(Error::CommunicationError(error_message(&err)));
+            }
+        }
+
         Ok(Self::Device {
             device: self.device,
+            connected: Arc::new(AtomicBool::new(true)),
+            descriptor_cache: DescriptorCache::default(),
         })
     }
 
@@ -296,6 +608,10 @@ impl UsbDeviceInfo for DeviceInfo {
         self.device.device_subclass()
     }
 
+    async fn protocol(&self) -> u8 {
+        self.device.device_protocol()
+    }
+
     async fn manufacturer_string(&self) -> Option<String> {
         self.device.manufacturer_name()
     }
@@ -303,28 +619,88 @@ impl UsbDeviceInfo for DeviceInfo {
     async fn product_string(&self) -> Option<String> {
         self.device.product_name()
     }
+
+    async fn serial_number_string(&self) -> Option<String> {
+        self.device.serial_number()
+    }
+
+    async fn device_version(&self) -> Version {
+        Version::new(
+            self.device.device_version_major(),
+            self.device.device_version_minor(),
+            self.device.device_version_subminor(),
+        )
+    }
+
+    async fn usb_version(&self) -> Option<Version> {
+        Some(Version::new(
+            self.device.usb_version_major(),
+            self.device.usb_version_minor(),
+            self.device.usb_version_subminor(),
+        ))
+    }
+
+    async fn speed(&self) -> Option<Speed> {
+        None
+    }
+
+    async fn bus_number(&self) -> Option<u8> {
+        None
+    }
+
+    async fn device_address(&self) -> Option<u8> {
+        None
+    }
+
+    async fn port_chain(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    async fn platform_path(&self) -> Option<String> {
+        None
+    }
+
+    async fn interfaces(&self) -> Vec<InterfaceFilterInfo> {
+        Vec::new()
+    }
 }
 
 impl UsbDevice for Device {
     type Interface = Interface;
 
     async fn open_interface(&self, number: u8) -> Result<Interface, Error> {
+        // Browsers don't always configure a device when it's opened, and no
+        // interface can be claimed until a configuration has been selected
+        if self.device.configuration().is_none() {
+            self.select_configuration(self.first_configuration()).await?;
+        }
+
         let dev_promise =
             JsFuture::from(Promise::resolve(&self.device.claim_interface(number))).await;
 
         // Wait for the interface to be claimed
         let _device: WasmUsbDevice = match dev_promise {
             Ok(dev) => dev.into(),
+            // Browsers report an interface which is held by something else
+            // as a network error
+            Err(err) if error_name(&err).as_deref() == Some("NetworkError") => {
+                return Err(Error::Busy { driver: None });
+            }
             Err(err) => {
-                return Err(Error::CommunicationError(
-                    err.as_string().unwrap_or_default(),
-                ));
+                return Err(Error::CommunicationError(format!(
+                    "failed to claim interface {number}: {}",
+                    error_message(&err),
+                )));
             }
         };
 
         Ok(Interface {
             device: self.device.clone(),
-            _number: number,
+            number,
+            cancellation: Cancellation::default(),
+            default_timeout: None,
+            retry: RetryPolicy::default(),
+            connected: self.connected.clone(),
         })
     }
 
@@ -332,7 +708,40 @@ impl UsbDevice for Device {
         self.open_interface(number).await
     }
 
+    async fn active_configuration(&self) -> Result<Option<u8>, Error> {
+        Ok(self
+            .device
+            .configuration()
+            .map(|configuration| configuration.configuration_value()))
+    }
+
+    async fn set_configuration(&self, value: u8) -> Result<(), Error> {
+        let Some(configuration) = self.device.configuration() else {
+            return self.select_configuration(value).await;
+        };
+
+        if configuration.configuration_value() == value {
+            return Ok(());
+        }
+
+        let claimed = configuration
+            .interfaces()
+            .iter()
+            .any(|interface| interface.unchecked_into::<web_sys::UsbInterface>().claimed());
+
+        if claimed {
+            return Err(Error::InterfacesClaimed);
+        }
+
+        self.select_configuration(value).await
+    }
+
+    async fn kernel_driver(&self, _interface: u8) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+
     async fn reset(&self) -> Result<(), Error> {
+        self.descriptor_cache.clear();
         let result = JsFuture::from(Promise::resolve(&self.device.reset())).await;
 
         match result {
@@ -354,6 +763,10 @@ impl UsbDevice for Device {
         }
     }
 
+    async fn is_open(&self) -> bool {
+        self.device.opened()
+    }
+
     async fn vendor_id(&self) -> u16 {
         self.device.vendor_id()
     }
@@ -370,6 +783,10 @@ impl UsbDevice for Device {
         self.device.device_subclass()
     }
 
+    async fn protocol(&self) -> u8 {
+        self.device.device_protocol()
+    }
+
     async fn manufacturer_string(&self) -> Option<String> {
         self.device.manufacturer_name()
     }
@@ -377,71 +794,265 @@ impl UsbDevice for Device {
     async fn product_string(&self) -> Option<String> {
         self.device.product_name()
     }
+
+    async fn serial_number_string(&self) -> Option<String> {
+        self.device.serial_number()
+    }
+
+    async fn device_version(&self) -> Version {
+        Version::new(
+            self.device.device_version_major(),
+            self.device.device_version_minor(),
+            self.device.device_version_subminor(),
+        )
+    }
+
+    async fn usb_version(&self) -> Option<Version> {
+        Some(Version::new(
+            self.device.usb_version_major(),
+            self.device.usb_version_minor(),
+            self.device.usb_version_subminor(),
+        ))
+    }
+
+    async fn speed(&self) -> Option<Speed> {
+        None
+    }
+
+    async fn bus_number(&self) -> Option<u8> {
+        None
+    }
+
+    async fn device_address(&self) -> Option<u8> {
+        None
+    }
+
+    async fn port_chain(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    async fn platform_path(&self) -> Option<String> {
+        None
+    }
 }
 
-impl<'a> UsbInterface<'a> for Interface {
-    async fn control_in(&self, data: crate::usb::ControlIn) -> Result<Vec<u8>, Error> {
+impl Device {
+    pub(crate) fn key(&self) -> DeviceKey {
+        DeviceKey(self.device.clone())
+    }
+
+    pub(crate) fn connected(&self) -> &Arc<AtomicBool> {
+        &self.connected
+    }
+
+    pub(crate) fn descriptor_cache(&self) -> &DescriptorCache {
+        &self.descriptor_cache
+    }
+
+    async fn select_configuration(&self, value: u8) -> Result<(), Error> {
+        self.descriptor_cache.clear();
+        let result = JsFuture::from(Promise::resolve(&self.device.select_configuration(value))).await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::CommunicationError(format!(
+                "failed to select configuration {value}: {}",
+                error_message(&err),
+            ))),
+        }
+    }
+
+    // The value of the first configuration the device reports, which is
+    // almost always 1
+    fn first_configuration(&self) -> u8 {
+        self.device
+            .configurations()
+            .get(0)
+            .dyn_into::<web_sys::UsbConfiguration>()
+            .map(|configuration| configuration.configuration_value())
+            .unwrap_or(1)
+    }
+
+    pub(crate) async fn configuration_descriptors(&self) -> Result<Vec<Vec<u8>>, Error> {
+        let count = self.device.configurations().length();
+
+        let mut descriptors = Vec::new();
+        for index in 0..count as u8 {
+            descriptors.push(self.configuration_descriptor_bytes(index).await?);
+        }
+
+        Ok(descriptors)
+    }
+
+    pub(crate) async fn control_in(&self, data: ControlIn) -> Result<Vec<u8>, Error> {
         let length = data.length;
         let params: UsbControlTransferParameters = data.into();
 
         let promise = Promise::resolve(&self.device.control_transfer_in(&params, length));
-        let result = JsFuture::from(promise).await;
+        let transfer_result: UsbInTransferResult = match JsFuture::from(promise).await {
+            Ok(res) => res.into(),
+            Err(err) => return Err(disconnect_error(&self.connected, transfer_error(err))),
+        };
+
+        check_status(transfer_result.status())?;
+
+        match transfer_result.data() {
+            Some(view) => Ok(view_bytes(&view)),
+            None => Err(Error::TransferError),
+        }
+    }
+
+    pub(crate) async fn control_out(&self, data: ControlOut<'_>) -> Result<usize, Error> {
+        let array = Uint8Array::from(data.data);
+        let array_obj = Object::try_from(&array).unwrap();
+        let params: UsbControlTransferParameters = data.into();
+
+        let promise = Promise::resolve(
+            &self
+                .device
+                .control_transfer_out_with_buffer_source(&params, array_obj),
+        );
+
+        let result: UsbOutTransferResult = match JsFuture::from(promise).await {
+            Ok(res) => res.into(),
+            Err(err) => return Err(disconnect_error(&self.connected, transfer_error(err))),
+        };
+
+        check_status(result.status())?;
+
+        Ok(result.bytes_written() as usize)
+    }
+
+    pub(crate) async fn get_descriptor(
+        &self,
+        descriptor_type: u8,
+        index: u8,
+        language: u16,
+        length: u16,
+    ) -> Result<Vec<u8>, Error> {
+        const REQUEST_GET_DESCRIPTOR: u8 = 0x06;
+
+        self.control_in(ControlIn {
+            control_type: ControlType::Standard,
+            recipient: Recipient::Device,
+            request: REQUEST_GET_DESCRIPTOR,
+            value: ((descriptor_type as u16) << 8) | index as u16,
+            index: language,
+            length,
+        })
+        .await
+    }
+}
+
+impl Interface {
+    // The WebUSB description of this interface in the active configuration
+    fn usb_interface(&self) -> Option<web_sys::UsbInterface> {
+        self.device
+            .configuration()?
+            .interfaces()
+            .iter()
+            .map(|interface| interface.unchecked_into::<web_sys::UsbInterface>())
+            .find(|interface| interface.interface_number() == self.number)
+    }
+
+    async fn control_in_with(&self, data: ControlIn, timeout: Option<Duration>) -> Result<Vec<u8>, Error> {
+        let length = data.length;
+        let params: UsbControlTransferParameters = data.into();
+
+        let promise = Promise::resolve(&self.device.control_transfer_in(&params, length));
+        let result = timer::bounded(timeout, self.cancellation.run(JsFuture::from(promise))).await?;
 
         let transfer_result: UsbInTransferResult = match result {
             Ok(res) => res.into(),
-            Err(_) => return Err(Error::TransferError),
+            Err(err) => return Err(disconnect_error(&self.connected, transfer_error(err))),
         };
 
+        check_status(transfer_result.status())?;
+
         let data = match transfer_result.data() {
-            Some(res) => res.buffer(),
+            Some(res) => view_bytes(&res),
             None => return Err(Error::TransferError),
         };
 
-        let array = Uint8Array::new(&data);
-
-        Ok(array.to_vec())
+        Ok(data)
     }
 
-    async fn control_out(&self, data: crate::usb::ControlOut<'a>) -> Result<usize, Error> {
+    async fn control_out_with(&self, data: ControlOut<'_>, timeout: Option<Duration>) -> Result<usize, Error> {
         let array = Uint8Array::from(data.data);
         let array_obj = Object::try_from(&array).unwrap();
         let params: UsbControlTransferParameters = data.into();
 
-        let result: UsbOutTransferResult = match JsFuture::from(Promise::resolve(
+        let promise = Promise::resolve(
             &self
                 .device
                 .control_transfer_out_with_buffer_source(&params, array_obj),
-        ))
-        .await
-        {
+        );
+
+        let result: UsbOutTransferResult = match timer::bounded(timeout, self.cancellation.run(JsFuture::from(promise))).await? {
             Ok(res) => res.into(),
-            Err(_) => return Err(Error::TransferError),
+            Err(err) => return Err(disconnect_error(&self.connected, transfer_error(err))),
         };
 
+        check_status(result.status())?;
+
         Ok(result.bytes_written() as usize)
     }
 
-    async fn bulk_in(&self, endpoint: u8, length: usize) -> Result<Vec<u8>, Error> {
+    async fn bulk_in_with(&self, endpoint: u8, length: usize, timeout: Option<Duration>) -> Result<TransferResult, Error> {
+        let (view, status) = self.bulk_in_view(endpoint, length, timeout).await?;
+        let data = view_bytes(&view);
+
+        Ok(TransferResult {
+            short_packet: data.len() < length,
+            data,
+            status,
+        })
+    }
+
+    async fn bulk_in_view(&self, endpoint: u8, length: usize, timeout: Option<Duration>) -> Result<(DataView, TransferStatus), Error> {
         let promise = Promise::resolve(&self.device.transfer_in(endpoint, length as u32));
 
-        let result = JsFuture::from(promise).await;
+        let result = match timer::bounded(timeout, self.cancellation.run(JsFuture::from(promise))).await {
+            Err(Error::Timeout) => {
+                self.resynchronize(UsbDirection::In, endpoint).await;
+                return Err(Error::Timeout);
+            }
+            result => result?,
+        };
 
         let transfer_result: UsbInTransferResult = match result {
             Ok(res) => res.into(),
-            Err(_) => return Err(Error::TransferError),
+            Err(err) => return Err(disconnect_error(&self.connected, transfer_error(err))),
         };
 
-        let data = match transfer_result.data() {
-            Some(res) => res.buffer(),
-            None => return Err(Error::TransferError),
+        let status = match transfer_result.status() {
+            UsbTransferStatus::Ok => TransferStatus::Completed,
+            UsbTransferStatus::Babble => TransferStatus::Babble,
+            UsbTransferStatus::Stall => return Err(Error::Stall),
+            _ => return Err(Error::TransferError),
         };
 
-        let array = Uint8Array::new(&data);
+        match transfer_result.data() {
+            Some(view) => Ok((view, status)),
+            None => Err(Error::TransferError),
+        }
+    }
 
-        Ok(array.to_vec())
+    pub(crate) async fn bulk_in_until(&self, endpoint: u8, length: usize, deadline: &Deadline) -> Result<Vec<u8>, Error> {
+        strict(
+            self.retry
+                .run_until(deadline, |remaining| self.bulk_in_with(endpoint, length, Some(remaining)))
+                .await?,
+        )
     }
 
-    async fn bulk_out(&self, endpoint: u8, data: &[u8]) -> Result<usize, Error> {
+    pub(crate) async fn bulk_out_until(&self, endpoint: u8, data: &[u8], deadline: &Deadline) -> Result<usize, Error> {
+        self.retry
+            .run_out_until(deadline, |remaining| self.bulk_out_with(endpoint, data, Some(remaining)))
+            .await
+    }
+
+    async fn bulk_out_with(&self, endpoint: u8, data: &[u8], timeout: Option<Duration>) -> Result<usize, Error> {
         let array = Uint8Array::from(data);
         let array_obj = Object::try_from(&array).unwrap();
 
@@ -451,14 +1062,219 @@ impl<'a> UsbInterface<'a> for Interface {
                 .transfer_out_with_buffer_source(endpoint, array_obj),
         );
 
-        let result = JsFuture::from(promise).await;
+        let result = match timer::bounded(timeout, self.cancellation.run(JsFuture::from(promise))).await {
+            Err(Error::Timeout) => {
+                self.resynchronize(UsbDirection::Out, endpoint).await;
+                return Err(Error::Timeout);
+            }
+            result => result?,
+        };
 
         let transfer_result: UsbOutTransferResult = match result {
             Ok(res) => res.into(),
-            Err(_) => return Err(Error::TransferError),
+            Err(err) => return Err(disconnect_error(&self.connected, transfer_error(err))),
         };
 
-        Ok(transfer_result.bytes_written() as usize)
+        let written = transfer_result.bytes_written() as usize;
+        if let Err(err) = check_status(transfer_result.status()) {
+            return Err(Error::partial(written, err));
+        }
+
+        Ok(written)
+    }
+
+    // WebUSB has no way to abort a transfer, so one which timed out is still
+    // queued on the endpoint and its late result is discarded. Clearing the
+    // halt condition resets the endpoint and its data toggle so the next
+    // transfer starts from a clean state as far as the device is concerned.
+    async fn resynchronize(&self, direction: UsbDirection, endpoint: u8) {
+        let promise = Promise::resolve(&self.device.clear_halt(direction, endpoint & 0x7f));
+        let _ = JsFuture::from(promise).await;
+    }
+}
+
+impl<'a> UsbInterface<'a> for Interface {
+    async fn control_in(&self, data: ControlIn) -> Result<Vec<u8>, Error> {
+        self.retry.run(|| self.control_in_with(data, self.default_timeout)).await
+    }
+
+    async fn control_out(&self, data: ControlOut<'a>) -> Result<usize, Error> {
+        self.retry.run_out(|| self.control_out_with(data, self.default_timeout)).await
+    }
+
+    async fn bulk_in(&self, endpoint: u8, length: usize) -> Result<Vec<u8>, Error> {
+        strict(self.retry.run(|| self.bulk_in_with(endpoint, length, self.default_timeout)).await?)
+    }
+
+    async fn bulk_in_into(&self, endpoint: u8, length: usize, buf: &mut Vec<u8>) -> Result<usize, Error> {
+        buf.clear();
+
+        let (view, status) = self.retry.run(|| self.bulk_in_view(endpoint, length, self.default_timeout)).await?;
+        if status == TransferStatus::Babble {
+            return Err(Error::Babble);
+        }
+
+        buf.resize(view.byte_length(), 0);
+        Uint8Array::new_with_byte_offset_and_length(
+            &view.buffer(),
+            view.byte_offset() as u32,
+            view.byte_length() as u32,
+        )
+        .copy_to(buf);
+
+        Ok(buf.len())
+    }
+
+    async fn bulk_in_result(&self, endpoint: u8, length: usize) -> Result<TransferResult, Error> {
+        self.retry.run(|| self.bulk_in_with(endpoint, length, self.default_timeout)).await
+    }
+
+    async fn bulk_out(&self, endpoint: u8, data: &[u8]) -> Result<usize, Error> {
+        self.retry.run_out(|| self.bulk_out_with(endpoint, data, self.default_timeout)).await
+    }
+
+    async fn bulk_out_owned(&self, endpoint: u8, data: Vec<u8>) -> Result<usize, Error> {
+        self.bulk_out(endpoint, &data).await
+    }
+
+    async fn control_in_timeout(&self, data: ControlIn, timeout: Duration) -> Result<Vec<u8>, Error> {
+        self.retry.run(|| self.control_in_with(data, Some(timeout))).await
+    }
+
+    async fn control_out_timeout(&self, data: ControlOut<'a>, timeout: Duration) -> Result<usize, Error> {
+        self.retry.run_out(|| self.control_out_with(data, Some(timeout))).await
+    }
+
+    async fn bulk_in_timeout(&self, endpoint: u8, length: usize, timeout: Duration) -> Result<Vec<u8>, Error> {
+        strict(self.retry.run(|| self.bulk_in_with(endpoint, length, Some(timeout))).await?)
+    }
+
+    async fn bulk_out_timeout(&self, endpoint: u8, data: &[u8], timeout: Duration) -> Result<usize, Error> {
+        self.retry.run_out(|| self.bulk_out_with(endpoint, data, Some(timeout))).await
+    }
+
+    fn set_default_timeout(&mut self, timeout: Option<Duration>) {
+        self.default_timeout = timeout;
+    }
+
+    fn default_timeout(&self) -> Option<Duration> {
+        self.default_timeout
+    }
+
+    fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    fn set_in_flight(&mut self, _endpoint: u8, _depth: usize) {}
+
+    fn in_flight(&self, _endpoint: u8) -> usize {
+        1
+    }
+
+    async fn clear_halt(&self, endpoint: u8) -> Result<(), Error> {
+        let direction = if endpoint & 0x80 != 0 {
+            UsbDirection::In
+        } else {
+            UsbDirection::Out
+        };
+
+        let promise = Promise::resolve(&self.device.clear_halt(direction, endpoint & 0x7f));
+        match JsFuture::from(promise).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(disconnect_error(&self.connected, transfer_error(err))),
+        }
+    }
+
+    fn interface_number(&self) -> u8 {
+        self.number
+    }
+
+    fn alt_settings(&self) -> Vec<AltSettingInfo> {
+        let Some(interface) = self.usb_interface() else {
+            return Vec::new();
+        };
+
+        interface
+            .alternates()
+            .iter()
+            .map(|setting| {
+                let setting: UsbAlternateInterface = setting.unchecked_into();
+                AltSettingInfo {
+                    alt_setting: setting.alternate_setting(),
+                    class: setting.interface_class(),
+                    subclass: setting.interface_subclass(),
+                    protocol: setting.interface_protocol(),
+                    num_endpoints: setting.endpoints().length() as u8,
+                }
+            })
+            .collect()
+    }
+
+    fn endpoints(&self) -> Vec<EndpointInfo> {
+        let Some(interface) = self.usb_interface() else {
+            return Vec::new();
+        };
+
+        interface
+            .alternate()
+            .endpoints()
+            .iter()
+            .map(|endpoint| {
+                let endpoint: UsbEndpoint = endpoint.unchecked_into();
+                let direction = match endpoint.direction() {
+                    UsbDirection::In => Direction::In,
+                    _ => Direction::Out,
+                };
+
+                EndpointInfo {
+                    address: match direction {
+                        Direction::In => endpoint.endpoint_number() | 0x80,
+                        Direction::Out => endpoint.endpoint_number(),
+                    },
+                    direction,
+                    transfer_type: match endpoint.type_() {
+                        UsbEndpointType::Isochronous => TransferType::Isochronous,
+                        UsbEndpointType::Interrupt => TransferType::Interrupt,
+                        _ => TransferType::Bulk,
+                    },
+                    max_packet_size: endpoint.packet_size() as usize,
+                    interval: None,
+                    interval_micros: None,
+                }
+            })
+            .collect()
+    }
+
+    async fn interface_string(&self) -> Result<Option<String>, Error> {
+        Ok(self
+            .usb_interface()
+            .and_then(|interface| interface.alternate().interface_name()))
+    }
+
+    async fn set_alt_setting(&self, alt_setting: u8) -> Result<(), Error> {
+        let promise = Promise::resolve(
+            &self.device.select_alternate_interface(self.number, alt_setting),
+        );
+
+        match JsFuture::from(promise).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::CommunicationError(format!(
+                "failed to select alternate setting {alt_setting}: {}",
+                error_message(&err),
+            ))),
+        }
+    }
+
+    fn cancel_all(&self) {
+        self.cancellation.cancel_all()
+    }
+
+    fn can_abort_transfers(&self) -> bool {
+        false
     }
 
     /*
@@ -490,6 +1306,68 @@ impl<'a> UsbInterface<'a> for Interface {
     */
 }
 
+// The `DataView` may only cover part of its buffer, so copy just that part.
+fn view_bytes(view: &DataView) -> Vec<u8> {
+    Uint8Array::new_with_byte_offset_and_length(
+        &view.buffer(),
+        view.byte_offset() as u32,
+        view.byte_length() as u32,
+    )
+    .to_vec()
+}
+
+fn check_status(status: UsbTransferStatus) -> Result<(), Error> {
+    match status {
+        UsbTransferStatus::Ok => Ok(()),
+        UsbTransferStatus::Stall => Err(Error::Stall),
+        UsbTransferStatus::Babble => Err(Error::Babble),
+        _ => Err(Error::TransferError),
+    }
+}
+
+// `bulk_in_result` reports babble in its status, everything else fails.
+fn strict(result: TransferResult) -> Result<Vec<u8>, Error> {
+    match result.status {
+        TransferStatus::Completed => Ok(result.data),
+        TransferStatus::Babble => Err(Error::Babble),
+    }
+}
+
+// Rejections are usually a `DOMException` rather than a string, so the
+// message has to be read from it.
+// Functions which are exported to JavaScript reject with an `Error` holding
+// the message
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+fn error_message(err: &JsValue) -> String {
+    if let Some(message) = err.as_string() {
+        return message;
+    }
+
+    js_sys::Reflect::get(err, &JsValue::from_str("message"))
+        .ok()
+        .and_then(|message| message.as_string())
+        .unwrap_or_default()
+}
+
+fn error_name(err: &JsValue) -> Option<String> {
+    js_sys::Reflect::get(err, &JsValue::from_str("name"))
+        .ok()
+        .and_then(|name| name.as_string())
+}
+
+fn transfer_error(err: JsValue) -> Error {
+    match error_name(&err).as_deref() {
+        // WebUSB rejects with a `NotFoundError` when the device has gone away
+        Some("NotFoundError") => Error::Disconnected,
+        _ => Error::TransferError,
+    }
+}
+
 impl From<ControlIn> for UsbControlTransferParameters {
     fn from(value: ControlIn) -> Self {
         UsbControlTransferParameters::new(
@@ -534,3 +1412,207 @@ impl From<ControlType> for UsbRequestType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    // Build a stand-in for a WebUSB object from a JS expression
+    fn mock(source: &str) -> JsValue {
+        js_sys::Function::new_no_args(&format!("return {source};"))
+            .call0(&JsValue::NULL)
+            .unwrap()
+    }
+
+    // A WebUSB device with the given IDs, which isn't open
+    fn mock_device(vendor_id: u16, product_id: u16) -> String {
+        format!(
+            "{{
+                vendorId: {vendor_id}, productId: {product_id},
+                deviceClass: 0, deviceSubclass: 0, deviceProtocol: 0,
+                deviceVersionMajor: 1, deviceVersionMinor: 0, deviceVersionSubminor: 0,
+                usbVersionMajor: 2, usbVersionMinor: 0, usbVersionSubminor: 0,
+                manufacturerName: null, productName: null, serialNumber: null,
+                opened: false, configuration: null, configurations: [],
+            }}"
+        )
+    }
+
+    // A `USB` object which has already been given access to `devices`, and
+    // which fails the test if it's asked to show the chooser
+    fn mock_usb(devices: &[String]) -> JsValue {
+        mock(&format!(
+            "{{
+                getDevices() {{ return Promise.resolve([{}]); }},
+                requestDevice() {{ throw new Error('requestDevice was called'); }},
+            }}",
+            devices.join(", "),
+        ))
+    }
+
+    #[wasm_bindgen_test]
+    fn view_bytes_copies_only_the_view() {
+        let bytes: Vec<u8> = (0..16).collect();
+        let buffer = Uint8Array::from(bytes.as_slice()).buffer();
+
+        let view = DataView::new(&buffer, 4, 8);
+        assert_eq!(view_bytes(&view), (4..12).collect::<Vec<u8>>());
+    }
+
+    #[wasm_bindgen_test]
+    async fn active_configuration_of_unconfigured_device() {
+        let device_info = DeviceInfo {
+            device: mock("{ opened: true, configuration: null }").unchecked_into(),
+        };
+        let device = device_info.open().await.unwrap();
+
+        assert_eq!(device.active_configuration().await, Ok(None));
+    }
+
+    #[wasm_bindgen_test]
+    async fn active_configuration_of_configured_device() {
+        let device_info = DeviceInfo {
+            device: mock("{ opened: true, configuration: { configurationValue: 2 } }").unchecked_into(),
+        };
+        let device = device_info.open().await.unwrap();
+
+        assert_eq!(device.active_configuration().await, Ok(Some(2)));
+    }
+
+    #[wasm_bindgen_test]
+    async fn select_configuration_clears_descriptor_cache() {
+        let device_info = DeviceInfo {
+            device: mock(
+                "{
+                    opened: true,
+                    configuration: { configurationValue: 1, interfaces: [] },
+                    selectConfiguration() { return Promise.resolve(); },
+                }",
+            )
+            .unchecked_into(),
+        };
+        let device = device_info.open().await.unwrap();
+
+        // Selecting the active configuration again changes nothing
+        device.descriptor_cache().insert((0x02, 0, 0, 9), vec![9, 0x02]);
+        device.set_configuration(1).await.unwrap();
+        assert!(device.descriptor_cache().get((0x02, 0, 0, 9)).is_some());
+
+        device.set_configuration(2).await.unwrap();
+        assert!(device.descriptor_cache().get((0x02, 0, 0, 9)).is_none());
+    }
+
+    #[wasm_bindgen_test]
+    async fn reset_clears_descriptor_cache() {
+        let device_info = DeviceInfo {
+            device: mock("{ opened: true, reset() { return Promise.resolve(); } }").unchecked_into(),
+        };
+        let device = device_info.open().await.unwrap();
+
+        device.descriptor_cache().insert((0x01, 0, 0, 18), vec![18, 0x01]);
+        device.reset().await.unwrap();
+        assert!(device.descriptor_cache().get((0x01, 0, 0, 18)).is_none());
+    }
+
+    #[wasm_bindgen_test]
+    async fn get_device_list_only_lists_paired_devices() {
+        set_usb_context(Some(mock_usb(&[mock_device(0x054c, 0x00c9), mock_device(0x046d, 0xc52b)])));
+
+        let filter = DeviceFilter {
+            vendor_id: Some(0x054c),
+            ..Default::default()
+        };
+        let devices = get_device_list(vec![filter]).await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].vendor_id().await, 0x054c);
+
+        // No matching device is paired, so there is nothing to list
+        let filter = DeviceFilter {
+            vendor_id: Some(0x1234),
+            ..Default::default()
+        };
+        assert_eq!(get_device_list(vec![filter]).await.unwrap_err(), Error::DeviceNotFound);
+
+        set_usb_context(None);
+    }
+
+    #[wasm_bindgen_test]
+    async fn get_device_reports_why_the_chooser_failed() {
+        let filter = DeviceFilter {
+            vendor_id: Some(0x054c),
+            ..Default::default()
+        };
+
+        let cases = [
+            ("NotFoundError", Error::DeviceNotFound),
+            ("SecurityError", Error::PermissionDenied),
+        ];
+        for (name, error) in cases {
+            set_usb_context(Some(mock(&format!(
+                "{{
+                    getDevices() {{ return Promise.resolve([]); }},
+                    requestDevice() {{ return Promise.reject({{ name: '{name}', message: '' }}); }},
+                }}"
+            ))));
+
+            assert_eq!(get_device(vec![filter.clone()]).await.unwrap_err(), error);
+        }
+
+        set_usb_context(None);
+    }
+
+    #[wasm_bindgen_test]
+    fn capabilities_match_the_target() {
+        // Listing every field makes this fail to build when one is added
+        let Capabilities {
+            enumerate_without_prompt,
+            detach_kernel_driver,
+            abort_transfers,
+            speed,
+            usb_version,
+            port_chain,
+            device_control_transfers,
+            hotplug,
+            interrupt_transfers,
+            isochronous_transfers,
+        } = capabilities();
+
+        assert!(!enumerate_without_prompt);
+        assert!(!detach_kernel_driver);
+        assert!(!abort_transfers);
+        assert!(!speed);
+        assert!(usb_version);
+        assert!(!port_chain);
+        assert!(device_control_transfers);
+        assert!(hotplug);
+        assert!(!interrupt_transfers);
+        assert!(!isochronous_transfers);
+    }
+
+    #[wasm_bindgen_test]
+    async fn no_included_devices_never_shows_the_chooser() {
+        set_usb_context(Some(mock_usb(&[mock_device(0x054c, 0x00c9)])));
+
+        let exclude_only = || {
+            vec![DeviceFilter {
+                vendor_id: Some(0x046d),
+                exclude: true,
+                ..Default::default()
+            }]
+        };
+
+        // The paired device isn't excluded, but isn't included either
+        for device_filters in [vec![], exclude_only()] {
+            assert_eq!(get_device(device_filters.clone()).await.unwrap_err(), Error::DeviceNotFound);
+            assert_eq!(request_device(device_filters.clone()).await.unwrap_err(), Error::DeviceNotFound);
+            assert!(filter::get_paired_devices(device_filters.clone()).await.unwrap().is_empty());
+
+            let request = PendingRequest::new(device_filters);
+            request.trigger();
+            assert_eq!(request.device().await.unwrap_err(), Error::DeviceNotFound);
+        }
+
+        set_usb_context(None);
+    }
+}
@@ -0,0 +1,77 @@
+//! Cancellation of transfers which are in progress on an interface.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+use crate::usb::Error;
+
+/// A handle shared by an interface and all of its clones, which can cancel
+/// every transfer running through it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Cancellation {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    generation: u64,
+    next_id: u64,
+    wakers: HashMap<u64, Waker>,
+}
+
+impl Cancellation {
+    /// Cancel every transfer currently running through this handle.
+    /// Transfers started afterwards are unaffected.
+    pub(crate) fn cancel_all(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+
+        for (_, waker) in state.wakers.drain() {
+            waker.wake();
+        }
+    }
+
+    /// Run `future` until it completes, or fail with [`Error::Cancelled`] if
+    /// [`Cancellation::cancel_all`] is called first. The future is dropped
+    /// when cancelled.
+    pub(crate) async fn run<F: Future>(&self, future: F) -> Result<F::Output, Error> {
+        let (generation, id) = {
+            let mut state = self.state.lock().unwrap();
+            state.next_id += 1;
+            (state.generation, state.next_id)
+        };
+
+        let _registration = Registration { cancellation: self, id };
+        let mut future = pin!(future);
+
+        std::future::poll_fn(|cx| {
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.generation != generation {
+                    return Poll::Ready(Err(Error::Cancelled));
+                }
+                state.wakers.insert(id, cx.waker().clone());
+            }
+
+            future.as_mut().poll(cx).map(Ok)
+        })
+        .await
+    }
+}
+
+/// Removes the waker of a transfer once it is no longer running.
+struct Registration<'a> {
+    cancellation: &'a Cancellation,
+    id: u64,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.cancellation.state.lock() {
+            state.wakers.remove(&self.id);
+        }
+    }
+}
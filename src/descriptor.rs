@@ -0,0 +1,1343 @@
+//! Parsing of the standard USB descriptors. Every backend hands the raw
+//! descriptors to the same parser, so they are described the same way on
+//! every target.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::usb::{
+    BosDescriptor, ConfigurationInfo, ControlIn, ControlType, DeviceCapability, DeviceDescriptor,
+    Error, FunctionInfo, InterfaceInfo, MsOsDescriptor, MsOsDescriptorSet, Recipient, Speed, UsbDevice, Version,
+};
+#[cfg(not(target_family = "wasm"))]
+use crate::usb::TransferType;
+use crate::{Device, Interface};
+
+const DESCRIPTOR_TYPE_DEVICE: u8 = 0x01;
+const DESCRIPTOR_TYPE_CONFIGURATION: u8 = 0x02;
+const DESCRIPTOR_TYPE_STRING: u8 = 0x03;
+const DESCRIPTOR_TYPE_INTERFACE: u8 = 0x04;
+const DESCRIPTOR_TYPE_BOS: u8 = 0x0F;
+const DESCRIPTOR_TYPE_INTERFACE_ASSOCIATION: u8 = 0x0B;
+const DESCRIPTOR_TYPE_DEVICE_CAPABILITY: u8 = 0x10;
+
+impl Device {
+    /// List the configurations of the device.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// for configuration in device.configurations().await.expect("Failed to read configurations") {
+    ///     println!(
+    ///         "Configuration {} has {} interfaces",
+    ///         configuration.value, configuration.num_interfaces
+    ///     );
+    /// }
+    /// # })
+    /// ```
+    pub async fn configurations(&self) -> Result<Vec<ConfigurationInfo>, Error> {
+        let super_speed = self.super_speed_power().await;
+
+        self.configuration_descriptors()
+            .await?
+            .iter()
+            .map(|descriptor| {
+                let mut configuration = ConfigurationInfo::parse(descriptor)?;
+                configuration.max_power_ma =
+                    super_speed.map(|super_speed| milliamps(configuration.max_power, super_speed));
+                Ok(configuration)
+            })
+            .collect()
+    }
+
+    /// Whether `bMaxPower` counts in the units of SuperSpeed, or `None` if
+    /// that can't be told.
+    ///
+    /// Without the speed the device is connected at, this approximates it
+    /// with the USB version the device supports. That's wrong for a USB 3
+    /// device which is plugged into a USB 2 port.
+    async fn super_speed_power(&self) -> Option<bool> {
+        match self.speed().await {
+            Some(speed) => Some(matches!(speed, Speed::Super | Speed::SuperPlus)),
+            None => Some(self.usb_version().await? >= Version::new(3, 0, 0)),
+        }
+    }
+
+    /// The maximum current the active configuration draws from the bus in
+    /// mA, or `None` if the device is unconfigured or neither its speed nor
+    /// its USB version is known.
+    ///
+    /// See [`ConfigurationInfo::max_power_ma`] for how this is approximated
+    /// when the speed isn't known.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// if let Some(current) = device.max_power_ma().await.expect("Failed to read configuration") {
+    ///     println!("The device draws up to {current} mA");
+    /// }
+    /// # })
+    /// ```
+    pub async fn max_power_ma(&self) -> Result<Option<u32>, Error> {
+        let Some(value) = self.active_configuration().await? else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .configurations()
+            .await?
+            .into_iter()
+            .find(|configuration| configuration.value == value)
+            .and_then(|configuration| configuration.max_power_ma))
+    }
+
+    /// Read the complete configuration descriptor at `index` (which counts
+    /// from 0, and isn't the configuration value), including the interface,
+    /// endpoint and class specific descriptors which follow it.
+    ///
+    /// Fails with [`Error::InvalidDescriptor`] if the device returns less
+    /// than the length it reports.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// let descriptor = device
+    ///     .configuration_descriptor_bytes(0)
+    ///     .await
+    ///     .expect("Failed to read configuration descriptor");
+    /// # })
+    /// ```
+    pub async fn configuration_descriptor_bytes(&self, index: u8) -> Result<Vec<u8>, Error> {
+        // Read the header first to find the length of the whole descriptor
+        let header = self.read_descriptor(DESCRIPTOR_TYPE_CONFIGURATION, index, 0, 9).await?;
+        let total_length = match header.get(2..4) {
+            Some(length) => u16::from_le_bytes([length[0], length[1]]),
+            None => return Err(Error::InvalidDescriptor),
+        };
+
+        let mut descriptor = self
+            .read_descriptor(DESCRIPTOR_TYPE_CONFIGURATION, index, 0, total_length)
+            .await?;
+
+        ConfigurationInfo::parse(&descriptor)?;
+        descriptor.truncate(total_length as usize);
+
+        Ok(descriptor)
+    }
+
+    /// Read the BOS descriptor, which lists capabilities such as the WebUSB
+    /// landing page of the device.
+    ///
+    /// Returns `None` for devices older than USB 2.1, which don't have one,
+    /// and for devices which stall the request.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// let bos = device.bos_descriptor().await.expect("Failed to read BOS descriptor");
+    /// if let Some(webusb) = bos.and_then(|bos| bos.webusb()) {
+    ///     println!("WebUSB requests use vendor code {:#04x}", webusb.vendor_code);
+    /// }
+    /// # })
+    /// ```
+    pub async fn bos_descriptor(&self) -> Result<Option<BosDescriptor>, Error> {
+        if self.device_descriptor().await?.usb_version < Version::new(2, 1, 0) {
+            return Ok(None);
+        }
+
+        // Read the header first to find the length of the whole descriptor
+        let header = match self.read_descriptor(DESCRIPTOR_TYPE_BOS, 0, 0, 5).await {
+            Ok(header) => header,
+            Err(Error::Stall) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let total_length = match header.get(2..4) {
+            Some(length) => u16::from_le_bytes([length[0], length[1]]),
+            None => return Err(Error::InvalidDescriptor),
+        };
+
+        let descriptor = self.read_descriptor(DESCRIPTOR_TYPE_BOS, 0, 0, total_length).await?;
+        BosDescriptor::parse(&descriptor).map(Some)
+    }
+
+    /// Open the interface of the active configuration with the class
+    /// `class`, and the subclass and protocol if they are given, instead of
+    /// by its number.
+    ///
+    /// Fails with [`Error::InterfaceNotFound`] if no interface matches, or
+    /// [`Error::AmbiguousInterface`] listing the numbers of the interfaces
+    /// which match if there is more than one.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// let interface = device
+    ///     .open_interface_by_class(0xFF, Some(0x42), None)
+    ///     .await
+    ///     .expect("Failed to open vendor interface");
+    /// # })
+    /// ```
+    pub async fn open_interface_by_class(
+        &self,
+        class: u8,
+        subclass: Option<u8>,
+        protocol: Option<u8>,
+    ) -> Result<Interface, Error> {
+        let candidates: Vec<u8> = self
+            .interfaces()
+            .await?
+            .into_iter()
+            .filter(|interface| {
+                interface.class == class
+                    && subclass.is_none_or(|subclass| interface.subclass == subclass)
+                    && protocol.is_none_or(|protocol| interface.protocol == protocol)
+            })
+            .map(|interface| interface.number)
+            .collect();
+
+        match candidates[..] {
+            [number] => self.open_interface(number).await,
+            [] => Err(Error::InterfaceNotFound),
+            _ => Err(Error::AmbiguousInterface(candidates)),
+        }
+    }
+
+    /// Read the Microsoft OS 2.0 descriptor set, using the vendor request
+    /// described by the platform capability in the BOS descriptor.
+    ///
+    /// Returns `None` if the device doesn't have the platform capability.
+    ///
+    /// **Note:** On Windows, this always fails because the OS only allows
+    /// control transfers through a claimed interface.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// use cross_usb::usb::MsOsDescriptor;
+    ///
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// let set = device.ms_os_descriptors().await.expect("Failed to read descriptors");
+    /// for descriptor in set.map(|set| set.descriptors).unwrap_or_default() {
+    ///     if let MsOsDescriptor::CompatibleId { interface, compatible_id, .. } = descriptor {
+    ///         println!("{interface:?} binds to {compatible_id}");
+    ///     }
+    /// }
+    /// # })
+    /// ```
+    pub async fn ms_os_descriptors(&self) -> Result<Option<MsOsDescriptorSet>, Error> {
+        const MS_OS_20_DESCRIPTOR_INDEX: u16 = 0x07;
+
+        let Some(platform) = self.bos_descriptor().await?.and_then(|bos| bos.ms_os_20()) else {
+            return Ok(None);
+        };
+
+        let descriptor = self
+            .control_in(ControlIn {
+                control_type: ControlType::Vendor,
+                recipient: Recipient::Device,
+                request: platform.vendor_code,
+                value: 0,
+                index: MS_OS_20_DESCRIPTOR_INDEX,
+                length: platform.total_length,
+            })
+            .await?;
+
+        MsOsDescriptorSet::parse(&descriptor).map(Some)
+    }
+
+    /// List the interfaces of the active configuration, in the order the
+    /// device describes them. This is empty if the device is unconfigured.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// // Find the first vendor specific interface
+    /// let interfaces = device.interfaces().await.expect("Failed to read interfaces");
+    /// let number = interfaces
+    ///     .iter()
+    ///     .find(|interface| interface.class == 0xFF)
+    ///     .map(|interface| interface.number)
+    ///     .expect("No vendor interface");
+    ///
+    /// let interface = device.open_interface(number).await.expect("Failed to open interface");
+    /// # })
+    /// ```
+    pub async fn interfaces(&self) -> Result<Vec<InterfaceInfo>, Error> {
+        match self.active_configuration_descriptor().await? {
+            Some(descriptor) => InterfaceInfo::parse_all(&descriptor),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// List the functions of the active configuration, grouping interfaces
+    /// by their interface association descriptors. This is empty if the
+    /// device is unconfigured.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// // Claim both interfaces of the first CDC serial port together
+    /// let functions = device.functions().await.expect("Failed to read functions");
+    /// let serial = functions
+    ///     .iter()
+    ///     .find(|function| function.class == 0x02)
+    ///     .expect("No serial port");
+    ///
+    /// let mut interfaces = Vec::new();
+    /// for number in &serial.interfaces {
+    ///     interfaces.push(device.open_interface(*number).await.expect("Failed to open interface"));
+    /// }
+    /// # })
+    /// ```
+    pub async fn functions(&self) -> Result<Vec<FunctionInfo>, Error> {
+        match self.active_configuration_descriptor().await? {
+            Some(descriptor) => FunctionInfo::parse_all(&descriptor),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Read the string descriptor at `index`, in `language` or the first
+    /// language the device supports if it's `None`.
+    ///
+    /// Returns `None` for index 0, which is never a string, and for strings
+    /// which are empty.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// // This device keeps its firmware build hash in string 0x20
+    /// let build = device.string_descriptor(0x20, None).await.expect("Failed to read string");
+    /// # })
+    /// ```
+    pub async fn string_descriptor(&self, index: u8, language: Option<u16>) -> Result<Option<String>, Error> {
+        if index == 0 {
+            return Ok(None);
+        }
+
+        let language = match language {
+            Some(language) => language,
+            None => self.default_language().await?,
+        };
+
+        let descriptor = self
+            .read_descriptor(DESCRIPTOR_TYPE_STRING, index, language, 255)
+            .await?;
+        parse_string(&descriptor)
+    }
+
+    /// List the language IDs (LANGIDs) the strings of the device are
+    /// available in, from string descriptor 0.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// const GERMAN: u16 = 0x0407;
+    /// let language = device
+    ///     .languages()
+    ///     .await
+    ///     .expect("Failed to read languages")
+    ///     .into_iter()
+    ///     .find(|language| *language == GERMAN);
+    ///
+    /// let product = device.product_string_in(language).await.expect("Failed to read string");
+    /// # })
+    /// ```
+    pub async fn languages(&self) -> Result<Vec<u16>, Error> {
+        let descriptor = self.read_descriptor(DESCRIPTOR_TYPE_STRING, 0, 0, 255).await?;
+        parse_languages(&descriptor)
+    }
+
+    /// Read the manufacturer string of the device in `language`, or in the
+    /// first language the device supports if it's `None`.
+    pub async fn manufacturer_string_in(&self, language: Option<u16>) -> Result<Option<String>, Error> {
+        const MANUFACTURER_INDEX: usize = 14;
+        self.device_string(MANUFACTURER_INDEX, language).await
+    }
+
+    /// Read the product string of the device in `language`, or in the first
+    /// language the device supports if it's `None`.
+    pub async fn product_string_in(&self, language: Option<u16>) -> Result<Option<String>, Error> {
+        const PRODUCT_INDEX: usize = 15;
+        self.device_string(PRODUCT_INDEX, language).await
+    }
+
+    /// Read the serial number string of the device in `language`, or in the
+    /// first language the device supports if it's `None`.
+    pub async fn serial_number_string_in(&self, language: Option<u16>) -> Result<Option<String>, Error> {
+        const SERIAL_NUMBER_INDEX: usize = 16;
+        self.device_string(SERIAL_NUMBER_INDEX, language).await
+    }
+
+    /// Read the string whose index is at `offset` in the device descriptor.
+    async fn device_string(&self, offset: usize, language: Option<u16>) -> Result<Option<String>, Error> {
+        let descriptor = self.device_descriptor_bytes().await?;
+        self.string_descriptor(descriptor[offset], language).await
+    }
+
+    /// Read the raw device descriptor, for fields which aren't described by
+    /// [`DeviceDescriptor`] or for diagnostics.
+    pub async fn device_descriptor_bytes(&self) -> Result<[u8; 18], Error> {
+        let descriptor = self.read_descriptor(DESCRIPTOR_TYPE_DEVICE, 0, 0, 18).await?;
+        if descriptor.get(1) != Some(&DESCRIPTOR_TYPE_DEVICE) {
+            return Err(Error::InvalidDescriptor);
+        }
+
+        descriptor.try_into().map_err(|_| Error::InvalidDescriptor)
+    }
+
+    /// Read the device descriptor.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// let descriptor = device.device_descriptor().await.expect("Failed to read descriptor");
+    /// println!("Endpoint 0 takes packets of {} bytes", descriptor.max_packet_size_0);
+    /// # })
+    /// ```
+    pub async fn device_descriptor(&self) -> Result<DeviceDescriptor, Error> {
+        Ok(DeviceDescriptor::parse(&self.device_descriptor_bytes().await?))
+    }
+
+    /// The largest packet the default control endpoint accepts
+    /// (`bMaxPacketSize0`), from the device descriptor.
+    ///
+    /// This is 8, 16, 32 or 64 bytes for full-speed devices, 64 for
+    /// high-speed devices and 512 for SuperSpeed devices, which report it as
+    /// a power of two (`9`).
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// let interface = device.open_interface(0).await.unwrap();
+    ///
+    /// let firmware = vec![0u8; 4096];
+    /// let chunk_size = device.max_packet_size_ep0().await.expect("Failed to read descriptor");
+    /// for chunk in firmware.chunks(chunk_size as usize) {
+    ///     interface.control_out(cross_usb::usb::ControlOut {
+    ///         control_type: cross_usb::usb::ControlType::Vendor,
+    ///         recipient: cross_usb::usb::Recipient::Device,
+    ///         request: 0x01,
+    ///         value: 0,
+    ///         index: 0,
+    ///         data: chunk,
+    ///     }).await.expect("Failed to write chunk");
+    /// }
+    /// # })
+    /// ```
+    pub async fn max_packet_size_ep0(&self) -> Result<u8, Error> {
+        Ok(self.device_descriptor().await?.max_packet_size_0)
+    }
+
+    /// The first language the device lists, or US English if the list can't
+    /// be read.
+    async fn default_language(&self) -> Result<u16, Error> {
+        first_language(self.languages().await)
+    }
+
+    /// Forget the descriptors which have been read from the device, so they
+    /// are read again the next time they are needed.
+    ///
+    /// Descriptors are cached because they normally never change. Call this
+    /// if the firmware of the device changes them without disconnecting,
+    /// such as after a firmware update.
+    pub fn invalidate_descriptor_cache(&self) {
+        self.descriptor_cache().clear();
+    }
+
+    /// Read a descriptor, unless it has been read before.
+    async fn read_descriptor(
+        &self,
+        descriptor_type: u8,
+        index: u8,
+        language: u16,
+        length: u16,
+    ) -> Result<Vec<u8>, Error> {
+        let key = (descriptor_type, index, language, length);
+        if let Some(descriptor) = self.descriptor_cache().get(key) {
+            return Ok(descriptor);
+        }
+
+        let descriptor = self.get_descriptor(descriptor_type, index, language, length).await?;
+        self.descriptor_cache().insert(key, descriptor.clone());
+
+        Ok(descriptor)
+    }
+
+    async fn active_configuration_descriptor(&self) -> Result<Option<Vec<u8>>, Error> {
+        let Some(value) = self.active_configuration().await? else {
+            return Ok(None);
+        };
+
+        for descriptor in self.configuration_descriptors().await? {
+            if ConfigurationInfo::parse(&descriptor)?.value == value {
+                return Ok(Some(descriptor));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// The descriptor type, index, language and length of a request.
+type DescriptorKey = (u8, u8, u16, u16);
+
+/// Descriptors which have already been read from a device, shared between
+/// its clones.
+///
+/// This is cleared when the configuration is changed or the device is reset,
+/// but not when an alternate setting is selected. Every alternate setting is
+/// described in the configuration descriptor, so selecting one doesn't change
+/// what any descriptor says.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DescriptorCache(Arc<Mutex<HashMap<DescriptorKey, Vec<u8>>>>);
+
+impl DescriptorCache {
+    pub(crate) fn get(&self, key: DescriptorKey) -> Option<Vec<u8>> {
+        self.0.lock().unwrap().get(&key).cloned()
+    }
+
+    pub(crate) fn insert(&self, key: DescriptorKey, descriptor: Vec<u8>) {
+        self.0.lock().unwrap().insert(key, descriptor);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+// Windows only allows control transfers through a claimed interface, so the
+// native backend reads the strings of interfaces through the interface
+#[cfg(not(target_family = "wasm"))]
+impl Interface {
+    /// Read the string descriptor at `index` in the first language the
+    /// device supports.
+    pub(crate) async fn string_descriptor(&self, index: u8) -> Result<Option<String>, Error> {
+        let languages = self.read_descriptor(DESCRIPTOR_TYPE_STRING, 0, 0, 255).await;
+        let language = first_language(languages.and_then(|descriptor| parse_languages(&descriptor)))?;
+
+        let descriptor = self
+            .read_descriptor(DESCRIPTOR_TYPE_STRING, index, language, 255)
+            .await?;
+        parse_string(&descriptor)
+    }
+
+    /// Read a descriptor, unless it has been read from the device before.
+    async fn read_descriptor(
+        &self,
+        descriptor_type: u8,
+        index: u8,
+        language: u16,
+        length: u16,
+    ) -> Result<Vec<u8>, Error> {
+        let key = (descriptor_type, index, language, length);
+        if let Some(descriptor) = self.descriptor_cache().get(key) {
+            return Ok(descriptor);
+        }
+
+        let descriptor = self.get_descriptor(descriptor_type, index, language, length).await?;
+        self.descriptor_cache().insert(key, descriptor.clone());
+
+        Ok(descriptor)
+    }
+}
+
+impl DeviceDescriptor {
+    /// Parse a device descriptor, which always has the same length.
+    ///
+    /// ```
+    /// use cross_usb::usb::{DeviceDescriptor, Version};
+    ///
+    /// let descriptor = DeviceDescriptor::parse(&[
+    ///     18, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 64, 0x4C, 0x05, 0xC9, 0x00, 0x00, 0x01, 1, 2, 0, 1,
+    /// ]);
+    ///
+    /// assert_eq!(descriptor.usb_version, Version::new(2, 0, 0));
+    /// assert_eq!(descriptor.max_packet_size_0, 64);
+    /// assert_eq!((descriptor.vendor_id, descriptor.product_id), (0x054C, 0x00C9));
+    /// assert_eq!(descriptor.serial_number_string_index, None);
+    ///
+    /// // A full-speed device with an 8 byte control endpoint
+    /// let descriptor = DeviceDescriptor::parse(&[
+    ///     18, 0x01, 0x10, 0x01, 0x00, 0x00, 0x00, 8, 0x83, 0x04, 0x11, 0xDF, 0x00, 0x22, 1, 2, 3, 1,
+    /// ]);
+    ///
+    /// assert_eq!(descriptor.usb_version, Version::new(1, 1, 0));
+    /// assert_eq!(descriptor.max_packet_size_0, 8);
+    /// assert_eq!(descriptor.serial_number_string_index, Some(3));
+    /// ```
+    pub fn parse(descriptor: &[u8; 18]) -> Self {
+        let word = |offset: usize| u16::from_le_bytes([descriptor[offset], descriptor[offset + 1]]);
+
+        Self {
+            length: descriptor[0],
+            descriptor_type: descriptor[1],
+            usb_version: Version::from_bcd(word(2)),
+            class: descriptor[4],
+            subclass: descriptor[5],
+            protocol: descriptor[6],
+            max_packet_size_0: descriptor[7],
+            vendor_id: word(8),
+            product_id: word(10),
+            device_version: Version::from_bcd(word(12)),
+            manufacturer_string_index: string_index(descriptor[14]),
+            product_string_index: string_index(descriptor[15]),
+            serial_number_string_index: string_index(descriptor[16]),
+            num_configurations: descriptor[17],
+        }
+    }
+}
+
+impl BosDescriptor {
+    /// Parse a full BOS descriptor, including its device capabilities.
+    ///
+    /// ```
+    /// use cross_usb::usb::BosDescriptor;
+    ///
+    /// let bos = BosDescriptor::parse(&[
+    ///     // BOS header, with one capability
+    ///     5, 0x0F, 29, 0, 1,
+    ///     // WebUSB platform capability
+    ///     24, 0x10, 0x05, 0x00, 0x38, 0xB6, 0x08, 0x34, 0xA9, 0x09, 0xA0, 0x47, 0x8B, 0xFD,
+    ///     0xA0, 0x76, 0x88, 0x15, 0xB6, 0x65, 0x00, 0x01, 0x21, 0x01,
+    /// ])
+    /// .unwrap();
+    ///
+    /// let webusb = bos.webusb().unwrap();
+    /// assert_eq!(webusb.vendor_code, 0x21);
+    /// assert_eq!(webusb.landing_page_index, Some(1));
+    /// ```
+    pub fn parse(descriptor: &[u8]) -> Result<Self, Error> {
+        if descriptor.len() < 5 || descriptor[1] != DESCRIPTOR_TYPE_BOS {
+            return Err(Error::InvalidDescriptor);
+        }
+
+        let total_length = u16::from_le_bytes([descriptor[2], descriptor[3]]) as usize;
+        if descriptor.len() < total_length || total_length < 5 {
+            return Err(Error::InvalidDescriptor);
+        }
+
+        let capabilities = split(&descriptor[5..total_length])?
+            .into_iter()
+            .filter(|capability| capability[1] == DESCRIPTOR_TYPE_DEVICE_CAPABILITY)
+            .map(DeviceCapability::parse)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { capabilities })
+    }
+}
+
+impl DeviceCapability {
+    /// Parse a single device capability descriptor.
+    fn parse(descriptor: &[u8]) -> Result<Self, Error> {
+        const USB_2_0_EXTENSION: u8 = 0x02;
+        const SUPERSPEED_USB: u8 = 0x03;
+        const PLATFORM: u8 = 0x05;
+
+        let Some((&capability_type, data)) = descriptor[2..].split_first() else {
+            return Err(Error::InvalidDescriptor);
+        };
+
+        Ok(match capability_type {
+            USB_2_0_EXTENSION => {
+                let attributes = data.get(..4).ok_or(Error::InvalidDescriptor)?;
+                DeviceCapability::Usb2Extension {
+                    attributes: u32::from_le_bytes(
+                        attributes.try_into().map_err(|_| Error::InvalidDescriptor)?,
+                    ),
+                }
+            }
+            SUPERSPEED_USB => {
+                let data = data.get(..7).ok_or(Error::InvalidDescriptor)?;
+                DeviceCapability::SuperSpeed {
+                    attributes: data[0],
+                    speeds_supported: u16::from_le_bytes([data[1], data[2]]),
+                    functionality_support: data[3],
+                    u1_exit_latency: data[4],
+                    u2_exit_latency: u16::from_le_bytes([data[5], data[6]]),
+                }
+            }
+            PLATFORM => {
+                // The UUID follows a reserved byte
+                let uuid = data.get(1..17).ok_or(Error::InvalidDescriptor)?;
+                DeviceCapability::Platform {
+                    uuid: uuid.try_into().map_err(|_| Error::InvalidDescriptor)?,
+                    data: data[17..].to_vec(),
+                }
+            }
+            _ => DeviceCapability::Other {
+                capability_type,
+                data: data.to_vec(),
+            },
+        })
+    }
+}
+
+impl MsOsDescriptorSet {
+    /// Parse a Microsoft OS 2.0 descriptor set, starting with its set header.
+    pub fn parse(descriptor: &[u8]) -> Result<Self, Error> {
+        const SET_HEADER: u16 = 0x00;
+        const SUBSET_HEADER_CONFIGURATION: u16 = 0x01;
+        const SUBSET_HEADER_FUNCTION: u16 = 0x02;
+        const FEATURE_COMPATIBLE_ID: u16 = 0x03;
+        const FEATURE_REG_PROPERTY: u16 = 0x04;
+
+        let word = |bytes: &[u8], offset: usize| -> Result<u16, Error> {
+            match bytes.get(offset..offset + 2) {
+                Some(word) => Ok(u16::from_le_bytes([word[0], word[1]])),
+                None => Err(Error::InvalidDescriptor),
+            }
+        };
+
+        if descriptor.len() < 10 || word(descriptor, 2)? != SET_HEADER {
+            return Err(Error::InvalidDescriptor);
+        }
+
+        let windows_version = u32::from_le_bytes(
+            descriptor[4..8].try_into().map_err(|_| Error::InvalidDescriptor)?,
+        );
+        let total_length = word(descriptor, 8)? as usize;
+        if descriptor.len() < total_length {
+            return Err(Error::InvalidDescriptor);
+        }
+
+        let mut descriptors = Vec::new();
+
+        // The function subset the current descriptor is in, and where it ends
+        let mut function: Option<(u8, usize)> = None;
+
+        let mut offset = word(descriptor, 0)? as usize;
+        while offset < total_length {
+            let length = word(descriptor, offset)? as usize;
+            if length < 4 || offset + length > total_length {
+                return Err(Error::InvalidDescriptor);
+            }
+
+            if function.is_some_and(|(_, end)| offset >= end) {
+                function = None;
+            }
+            let interface = function.map(|(interface, _)| interface);
+
+            let body = &descriptor[offset + 4..offset + length];
+            match word(descriptor, offset + 2)? {
+                SUBSET_HEADER_CONFIGURATION => function = None,
+                SUBSET_HEADER_FUNCTION => {
+                    let first_interface = *body.first().ok_or(Error::InvalidDescriptor)?;
+                    let subset_length = word(body, 2)? as usize;
+                    function = Some((first_interface, offset + subset_length));
+                }
+                FEATURE_COMPATIBLE_ID => {
+                    let ids = body.get(..16).ok_or(Error::InvalidDescriptor)?;
+                    descriptors.push(MsOsDescriptor::CompatibleId {
+                        interface,
+                        compatible_id: ascii_id(&ids[..8]),
+                        sub_compatible_id: ascii_id(&ids[8..]),
+                    });
+                }
+                FEATURE_REG_PROPERTY => {
+                    let data_type = word(body, 0)?;
+                    let name_length = word(body, 2)? as usize;
+                    let name = body.get(4..4 + name_length).ok_or(Error::InvalidDescriptor)?;
+                    let data_length = word(body, 4 + name_length)? as usize;
+                    let data_start = 6 + name_length;
+                    let data = body
+                        .get(data_start..data_start + data_length)
+                        .ok_or(Error::InvalidDescriptor)?;
+
+                    descriptors.push(MsOsDescriptor::RegistryProperty {
+                        interface,
+                        data_type,
+                        name: utf16_name(name),
+                        data: data.to_vec(),
+                    });
+                }
+                descriptor_type => descriptors.push(MsOsDescriptor::Other {
+                    interface,
+                    descriptor_type,
+                    data: body.to_vec(),
+                }),
+            }
+
+            offset += length;
+        }
+
+        Ok(Self {
+            windows_version,
+            descriptors,
+        })
+    }
+}
+
+/// Decode an ASCII ID which is padded with zeros.
+fn ascii_id(id: &[u8]) -> String {
+    let end = id.iter().position(|byte| *byte == 0).unwrap_or(id.len());
+    String::from_utf8_lossy(&id[..end]).into_owned()
+}
+
+/// Decode a UTF-16LE name which ends with a zero.
+fn utf16_name(name: &[u8]) -> String {
+    let units: Vec<u16> = name
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect();
+
+    String::from_utf16_lossy(&units)
+}
+
+impl ConfigurationInfo {
+    /// Parse a full configuration descriptor, as returned by the device.
+    ///
+    /// ```
+    /// use cross_usb::usb::{ConfigurationInfo, Error};
+    ///
+    /// let descriptor = [
+    ///     9, 0x02, 18, 0, 1, 1, 0, 0x80, 50,
+    ///     9, 0x04, 0, 0, 0, 0xFF, 0, 0, 0,
+    /// ];
+    ///
+    /// let configuration = ConfigurationInfo::parse(&descriptor).unwrap();
+    /// assert_eq!(configuration.value, 1);
+    /// assert_eq!(configuration.max_power, 50);
+    /// assert!(!configuration.self_powered);
+    ///
+    /// // The descriptor is shorter than its wTotalLength
+    /// assert_eq!(ConfigurationInfo::parse(&descriptor[..12]), Err(Error::InvalidDescriptor));
+    /// ```
+    pub fn parse(descriptor: &[u8]) -> Result<Self, Error> {
+        if descriptor.len() < 9 || descriptor[1] != DESCRIPTOR_TYPE_CONFIGURATION {
+            return Err(Error::InvalidDescriptor);
+        }
+
+        let total_length = u16::from_le_bytes([descriptor[2], descriptor[3]]) as usize;
+        if descriptor.len() < total_length {
+            return Err(Error::InvalidDescriptor);
+        }
+
+        Ok(Self {
+            value: descriptor[5],
+            string_index: string_index(descriptor[6]),
+            num_interfaces: descriptor[4],
+            attributes: descriptor[7],
+            max_power: descriptor[8],
+            self_powered: descriptor[7] & 0x40 != 0,
+            max_power_ma: None,
+        })
+    }
+}
+
+impl InterfaceInfo {
+    /// Parse every interface in a full configuration descriptor. Alternate
+    /// settings are counted towards the interface they belong to, whose class
+    /// and string are taken from alternate setting 0.
+    ///
+    /// ```
+    /// use cross_usb::usb::InterfaceInfo;
+    ///
+    /// let interfaces = InterfaceInfo::parse_all(&[
+    ///     9, 0x02, 36, 0, 2, 1, 0, 0x80, 50,
+    ///     // Interface 0, with an alternate setting
+    ///     9, 0x04, 0, 0, 0, 0x0E, 0x02, 0, 4,
+    ///     9, 0x04, 0, 1, 0, 0x0E, 0x02, 0, 0,
+    ///     // Interface 1
+    ///     9, 0x04, 1, 0, 0, 0xFF, 0, 0, 0,
+    /// ])
+    /// .unwrap();
+    ///
+    /// assert_eq!(interfaces.len(), 2);
+    /// assert_eq!(interfaces[0].num_alt_settings, 2);
+    /// assert_eq!(interfaces[0].string_index, Some(4));
+    /// assert_eq!(interfaces[1].class, 0xFF);
+    /// ```
+    pub fn parse_all(configuration: &[u8]) -> Result<Vec<Self>, Error> {
+        let mut interfaces: Vec<Self> = Vec::new();
+
+        for descriptor in split(configuration)? {
+            if descriptor[1] != DESCRIPTOR_TYPE_INTERFACE {
+                continue;
+            }
+
+            if descriptor.len() < 9 {
+                return Err(Error::InvalidDescriptor);
+            }
+
+            let number = descriptor[2];
+            match interfaces.iter_mut().find(|interface| interface.number == number) {
+                Some(interface) => interface.num_alt_settings = interface.num_alt_settings.saturating_add(1),
+                None => interfaces.push(Self {
+                    number,
+                    class: descriptor[5],
+                    subclass: descriptor[6],
+                    protocol: descriptor[7],
+                    string_index: string_index(descriptor[8]),
+                    num_alt_settings: 1,
+                }),
+            }
+        }
+
+        Ok(interfaces)
+    }
+}
+
+impl FunctionInfo {
+    /// Parse every function in a full configuration descriptor. Each
+    /// interface association descriptor starts a function, and interfaces
+    /// which no association includes become functions of their own.
+    ///
+    /// ```
+    /// use cross_usb::usb::FunctionInfo;
+    ///
+    /// // A CDC serial port and a vendor specific interface
+    /// let functions = FunctionInfo::parse_all(&[
+    ///     9, 0x02, 98, 0, 3, 1, 0, 0x80, 50,
+    ///     // Association of interfaces 0 and 1
+    ///     8, 0x0B, 0, 2, 0x02, 0x02, 0x01, 5,
+    ///     // Interface 0, with its CDC functional descriptors and interrupt endpoint
+    ///     9, 0x04, 0, 0, 1, 0x02, 0x02, 0x01, 0,
+    ///     5, 0x24, 0x00, 0x10, 0x01,
+    ///     5, 0x24, 0x01, 0x00, 0x01,
+    ///     4, 0x24, 0x02, 0x02,
+    ///     5, 0x24, 0x06, 0x00, 0x01,
+    ///     7, 0x05, 0x83, 0x03, 8, 0, 16,
+    ///     // Interface 1, with its bulk endpoints
+    ///     9, 0x04, 1, 0, 2, 0x0A, 0x00, 0x00, 0,
+    ///     7, 0x05, 0x01, 0x02, 64, 0, 0,
+    ///     7, 0x05, 0x81, 0x02, 64, 0, 0,
+    ///     // Interface 2, with its bulk endpoints
+    ///     9, 0x04, 2, 0, 2, 0xFF, 0x00, 0x00, 6,
+    ///     7, 0x05, 0x02, 0x02, 64, 0, 0,
+    ///     7, 0x05, 0x82, 0x02, 64, 0, 0,
+    /// ])
+    /// .unwrap();
+    ///
+    /// assert_eq!(functions.len(), 2);
+    /// assert_eq!(functions[0].interfaces, [0, 1]);
+    /// assert_eq!((functions[0].class, functions[0].subclass), (0x02, 0x02));
+    /// assert_eq!(functions[0].string_index, Some(5));
+    /// assert_eq!(functions[1].interfaces, [2]);
+    /// assert_eq!(functions[1].class, 0xFF);
+    /// ```
+    pub fn parse_all(configuration: &[u8]) -> Result<Vec<Self>, Error> {
+        let mut functions: Vec<Self> = Vec::new();
+
+        for descriptor in split(configuration)? {
+            match descriptor[1] {
+                DESCRIPTOR_TYPE_INTERFACE_ASSOCIATION => {
+                    if descriptor.len() < 8 {
+                        return Err(Error::InvalidDescriptor);
+                    }
+
+                    let first = descriptor[2];
+                    functions.push(Self {
+                        interfaces: (0..descriptor[3])
+                            .map_while(|offset| first.checked_add(offset))
+                            .collect(),
+                        class: descriptor[4],
+                        subclass: descriptor[5],
+                        protocol: descriptor[6],
+                        string_index: string_index(descriptor[7]),
+                    });
+                }
+                DESCRIPTOR_TYPE_INTERFACE => {
+                    if descriptor.len() < 9 {
+                        return Err(Error::InvalidDescriptor);
+                    }
+
+                    // Alternate settings and associated interfaces are
+                    // already part of a function
+                    let number = descriptor[2];
+                    if functions.iter().any(|function| function.interfaces.contains(&number)) {
+                        continue;
+                    }
+
+                    functions.push(Self {
+                        interfaces: vec![number],
+                        class: descriptor[5],
+                        subclass: descriptor[6],
+                        protocol: descriptor[7],
+                        string_index: string_index(descriptor[8]),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(functions)
+    }
+}
+
+/// Check the header of a string descriptor, returning the bytes after it.
+fn string_body(descriptor: &[u8]) -> Result<&[u8], Error> {
+    let length = match descriptor.first() {
+        Some(length) => *length as usize,
+        None => return Err(Error::InvalidDescriptor),
+    };
+
+    if length < 2 || length > descriptor.len() || descriptor[1] != DESCRIPTOR_TYPE_STRING {
+        return Err(Error::InvalidDescriptor);
+    }
+
+    Ok(&descriptor[2..length])
+}
+
+/// Decode a string descriptor, whose text is UTF-16LE.
+fn parse_string(descriptor: &[u8]) -> Result<Option<String>, Error> {
+    if descriptor.is_empty() {
+        return Ok(None);
+    }
+
+    let body = string_body(descriptor)?;
+    if body.len() % 2 != 0 {
+        return Err(Error::InvalidDescriptor);
+    }
+
+    let text: Vec<u16> = body
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+
+    Ok((!text.is_empty()).then(|| String::from_utf16_lossy(&text)))
+}
+
+/// Decode string descriptor 0, which lists the supported language IDs. A
+/// device which has strings must list at least one language.
+fn parse_languages(descriptor: &[u8]) -> Result<Vec<u16>, Error> {
+    let body = string_body(descriptor)?;
+    if body.is_empty() || body.len() % 2 != 0 {
+        return Err(Error::InvalidDescriptor);
+    }
+
+    Ok(body
+        .chunks_exact(2)
+        .map(|language| u16::from_le_bytes([language[0], language[1]]))
+        .collect())
+}
+
+/// The first language a device lists, or US English if the list can't be
+/// read.
+fn first_language(languages: Result<Vec<u16>, Error>) -> Result<u16, Error> {
+    const US_ENGLISH: u16 = 0x0409;
+
+    match languages {
+        Ok(languages) => Ok(languages[0]),
+        Err(Error::Disconnected) => Err(Error::Disconnected),
+        Err(_) => Ok(US_ENGLISH),
+    }
+}
+
+/// Split a run of descriptors, such as a full configuration descriptor, into
+/// the individual descriptors.
+fn split(mut bytes: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    let mut descriptors = Vec::new();
+
+    while !bytes.is_empty() {
+        let length = bytes[0] as usize;
+        if length < 2 || length > bytes.len() {
+            return Err(Error::InvalidDescriptor);
+        }
+
+        let (descriptor, rest) = bytes.split_at(length);
+        descriptors.push(descriptor);
+        bytes = rest;
+    }
+
+    Ok(descriptors)
+}
+
+/// Decode the `bInterval` of an endpoint into the time between polls in
+/// microseconds. Full and low speed interrupt endpoints count in frames of
+/// 1 ms, everything else uses an exponent of frames or 125 µs microframes.
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn interval_micros(
+    transfer_type: TransferType,
+    interval: u8,
+    speed: Option<Speed>,
+) -> Option<u32> {
+    let exponent = |unit: u32| {
+        let interval = interval.clamp(1, 16) as u32;
+        Some(unit << (interval - 1))
+    };
+
+    match (transfer_type, speed?) {
+        (TransferType::Interrupt, Speed::Low | Speed::Full) if interval > 0 => {
+            Some(interval as u32 * 1000)
+        }
+        (TransferType::Isochronous, Speed::Full) => exponent(1000),
+        (
+            TransferType::Interrupt | TransferType::Isochronous,
+            Speed::High | Speed::Super | Speed::SuperPlus,
+        ) => exponent(125),
+        _ => None,
+    }
+}
+
+/// Convert `bMaxPower` to mA, which counts in units of 8 mA when the device
+/// is connected at SuperSpeed and 2 mA otherwise.
+fn milliamps(max_power: u8, super_speed: bool) -> u32 {
+    let unit = if super_speed { 8 } else { 2 };
+    max_power as u32 * unit
+}
+
+/// String descriptor indexes use 0 for no string.
+fn string_index(index: u8) -> Option<u8> {
+    (index != 0).then_some(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The configuration of the Linux `g_acm_ms` gadget, a CDC serial port and
+    // mass storage, as it's described at full speed
+    const ACM_MS: &[u8] = &[
+        9, 0x02, 98, 0, 3, 1, 0, 0xC0, 1,
+        // CDC ACM
+        8, 0x0B, 0, 2, 0x02, 0x02, 0x01, 6,
+        9, 0x04, 0, 0, 1, 0x02, 0x02, 0x01, 4,
+        5, 0x24, 0x00, 0x10, 0x01,
+        5, 0x24, 0x01, 0x00, 1,
+        4, 0x24, 0x02, 0x02,
+        5, 0x24, 0x06, 0, 1,
+        7, 0x05, 0x82, 0x03, 10, 0, 32,
+        9, 0x04, 1, 0, 2, 0x0A, 0x00, 0x00, 5,
+        7, 0x05, 0x81, 0x02, 64, 0, 0,
+        7, 0x05, 0x01, 0x02, 64, 0, 0,
+        // Mass storage, with no association
+        9, 0x04, 2, 0, 2, 0x08, 0x06, 0x50, 7,
+        7, 0x05, 0x83, 0x02, 64, 0, 0,
+        7, 0x05, 0x02, 0x02, 64, 0, 0,
+    ];
+
+    // The CDC configuration of the Linux `g_multi` gadget, an ECM network
+    // adapter, a CDC serial port and mass storage, as it's described at full
+    // speed
+    const MULTI: &[u8] = &[
+        9, 0x02, 177, 0, 5, 1, 5, 0xC0, 1,
+        // CDC ECM, whose data interface only has endpoints in alternate
+        // setting 1
+        8, 0x0B, 0, 2, 0x02, 0x06, 0x00, 9,
+        9, 0x04, 0, 0, 1, 0x02, 0x06, 0x00, 6,
+        5, 0x24, 0x00, 0x10, 0x01,
+        5, 0x24, 0x06, 0, 1,
+        13, 0x24, 0x0F, 7, 0, 0, 0, 0, 0xEA, 0x05, 0, 0, 0,
+        7, 0x05, 0x82, 0x03, 16, 0, 32,
+        9, 0x04, 1, 0, 0, 0x0A, 0x00, 0x00, 0,
+        9, 0x04, 1, 1, 2, 0x0A, 0x00, 0x00, 8,
+        7, 0x05, 0x81, 0x02, 64, 0, 0,
+        7, 0x05, 0x01, 0x02, 64, 0, 0,
+        // CDC ACM
+        8, 0x0B, 2, 2, 0x02, 0x02, 0x01, 12,
+        9, 0x04, 2, 0, 1, 0x02, 0x02, 0x01, 10,
+        5, 0x24, 0x00, 0x10, 0x01,
+        5, 0x24, 0x01, 0x00, 3,
+        4, 0x24, 0x02, 0x02,
+        5, 0x24, 0x06, 2, 3,
+        7, 0x05, 0x84, 0x03, 10, 0, 32,
+        9, 0x04, 3, 0, 2, 0x0A, 0x00, 0x00, 11,
+        7, 0x05, 0x83, 0x02, 64, 0, 0,
+        7, 0x05, 0x02, 0x02, 64, 0, 0,
+        // Mass storage, with no association
+        9, 0x04, 4, 0, 2, 0x08, 0x06, 0x50, 13,
+        7, 0x05, 0x85, 0x02, 64, 0, 0,
+        7, 0x05, 0x03, 0x02, 64, 0, 0,
+    ];
+
+    #[test]
+    fn functions_of_acm_and_mass_storage() {
+        let functions = FunctionInfo::parse_all(ACM_MS).unwrap();
+
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0].interfaces, [0, 1]);
+        assert_eq!(
+            (functions[0].class, functions[0].subclass, functions[0].protocol),
+            (0x02, 0x02, 0x01),
+        );
+        assert_eq!(functions[0].string_index, Some(6));
+        assert_eq!(functions[1].interfaces, [2]);
+        assert_eq!(
+            (functions[1].class, functions[1].subclass, functions[1].protocol),
+            (0x08, 0x06, 0x50),
+        );
+        assert_eq!(functions[1].string_index, Some(7));
+    }
+
+    #[test]
+    fn functions_of_multi() {
+        let functions = FunctionInfo::parse_all(MULTI).unwrap();
+
+        let interfaces: Vec<_> = functions.iter().map(|function| function.interfaces.clone()).collect();
+        assert_eq!(interfaces, [vec![0, 1], vec![2, 3], vec![4]]);
+
+        let classes: Vec<_> = functions.iter().map(|function| (function.class, function.subclass)).collect();
+        assert_eq!(classes, [(0x02, 0x06), (0x02, 0x02), (0x08, 0x06)]);
+
+        // The alternate setting of the ECM data interface stays part of its
+        // function
+        let interfaces = InterfaceInfo::parse_all(MULTI).unwrap();
+        assert_eq!(interfaces.len(), 5);
+        assert_eq!(interfaces[1].num_alt_settings, 2);
+        assert_eq!(interfaces[1].string_index, None);
+    }
+
+    #[test]
+    fn configuration_of_multi() {
+        let configuration = ConfigurationInfo::parse(MULTI).unwrap();
+
+        assert_eq!(configuration.num_interfaces, 5);
+        assert_eq!(configuration.string_index, Some(5));
+        assert!(configuration.self_powered);
+    }
+
+    const DEVICE_INTERFACE_GUID: &str = "{88BAE032-5A81-49F0-BC3D-A4FF138216D6}";
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    // An MS OS 2.0 descriptor set which binds WinUSB to the function starting
+    // at interface 2, like the example in Microsoft's specification, followed
+    // by a vendor revision for the whole configuration
+    fn ms_os_set() -> Vec<u8> {
+        let name = utf16("DeviceInterfaceGUIDs\0");
+        let data = utf16(&format!("{DEVICE_INTERFACE_GUID}\0\0"));
+
+        let mut set = vec![
+            // Set header, for Windows 8.1
+            10, 0, 0x00, 0, 0x00, 0x00, 0x03, 0x06, 184, 0,
+            // Configuration subset
+            8, 0, 0x01, 0, 0, 0, 174, 0,
+            // Function subset
+            8, 0, 0x02, 0, 2, 0, 160, 0,
+            // Compatible ID
+            20, 0, 0x03, 0, b'W', b'I', b'N', b'U', b'S', b'B', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            // Registry property, of type REG_MULTI_SZ
+            132, 0, 0x04, 0, 7, 0, name.len() as u8, 0,
+        ];
+        set.extend_from_slice(&name);
+        set.extend_from_slice(&[data.len() as u8, 0]);
+        set.extend_from_slice(&data);
+        // Vendor revision, after the function subset ends
+        set.extend_from_slice(&[6, 0, 0x08, 0, 1, 0]);
+
+        set
+    }
+
+    #[test]
+    fn ms_os_set_with_a_function_subset() {
+        let set = MsOsDescriptorSet::parse(&ms_os_set()).unwrap();
+
+        assert_eq!(set.windows_version, 0x0603_0000);
+        assert_eq!(
+            set.descriptors,
+            [
+                MsOsDescriptor::CompatibleId {
+                    interface: Some(2),
+                    compatible_id: "WINUSB".to_string(),
+                    sub_compatible_id: String::new(),
+                },
+                MsOsDescriptor::RegistryProperty {
+                    interface: Some(2),
+                    data_type: 7,
+                    name: "DeviceInterfaceGUIDs".to_string(),
+                    data: utf16(&format!("{DEVICE_INTERFACE_GUID}\0\0")),
+                },
+                MsOsDescriptor::Other {
+                    interface: None,
+                    descriptor_type: 0x08,
+                    data: vec![1, 0],
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn ms_os_set_with_broken_lengths() {
+        let set = ms_os_set();
+
+        // Cut off before wTotalLength
+        assert_eq!(MsOsDescriptorSet::parse(&set[..100]), Err(Error::InvalidDescriptor));
+
+        // The registry property says it runs past the end of the set
+        let mut overlong = set.clone();
+        overlong[46] = 200;
+        assert_eq!(MsOsDescriptorSet::parse(&overlong), Err(Error::InvalidDescriptor));
+
+        // A feature which is shorter than its own header
+        let mut short = set;
+        short[46] = 2;
+        assert_eq!(MsOsDescriptorSet::parse(&short), Err(Error::InvalidDescriptor));
+    }
+
+    #[test]
+    fn languages_of_a_broken_string_descriptor() {
+        assert_eq!(parse_languages(&[4, 0x03, 0x09, 0x04]), Ok(vec![0x0409]));
+
+        // No languages, which `default_language` can't pick from
+        assert_eq!(parse_languages(&[2, 0x03]), Err(Error::InvalidDescriptor));
+
+        // Half of a second language
+        assert_eq!(parse_languages(&[5, 0x03, 0x09, 0x04, 0]), Err(Error::InvalidDescriptor));
+
+        // A configuration descriptor rather than a string descriptor
+        assert_eq!(parse_languages(&[4, 0x02, 0x09, 0x04]), Err(Error::InvalidDescriptor));
+    }
+
+    #[test]
+    fn descriptor_cache_keys_on_every_field() {
+        let cache = DescriptorCache::default();
+        cache.insert((0x03, 1, 0x0409, 255), vec![1]);
+
+        assert_eq!(cache.get((0x03, 1, 0x0409, 255)), Some(vec![1]));
+        assert_eq!(cache.get((0x02, 1, 0x0409, 255)), None);
+        assert_eq!(cache.get((0x03, 2, 0x0409, 255)), None);
+        assert_eq!(cache.get((0x03, 1, 0x0407, 255)), None);
+
+        // A shorter read of the same descriptor is a different request
+        assert_eq!(cache.get((0x03, 1, 0x0409, 4)), None);
+    }
+
+    #[test]
+    fn descriptor_cache_clear_is_shared_between_clones() {
+        let cache = DescriptorCache::default();
+        let clone = cache.clone();
+        cache.insert((0x02, 0, 0, 9), vec![9, 0x02]);
+
+        assert_eq!(clone.get((0x02, 0, 0, 9)), Some(vec![9, 0x02]));
+
+        clone.clear();
+        assert_eq!(cache.get((0x02, 0, 0, 9)), None);
+    }
+}
@@ -0,0 +1,154 @@
+//! Owned handles to a single endpoint of an [`Interface`], for reading and
+//! writing from separate tasks at the same time.
+
+use std::time::Duration;
+
+use crate::io::{BulkReader, BulkSink, BulkWriter};
+use crate::usb::{Error, TransferResult, UsbInterface};
+use crate::Interface;
+
+impl Interface {
+    /// Split this interface into an [`InEndpointHandle`] for the bulk IN
+    /// endpoint `in_endpoint` and an [`OutEndpointHandle`] for the bulk OUT
+    /// endpoint `out_endpoint`.
+    ///
+    /// Each handle owns its own reference to the interface, so they can be
+    /// moved into separate tasks for full-duplex communication. Transfers
+    /// started through both handles at once are queued by the platform.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// let interface = device.open_interface(0).await.expect("Failed to open interface");
+    /// let (input, output) = interface.split(0x81, 0x02);
+    ///
+    /// let (reply, written) = futures::join!(
+    ///     input.read(64),
+    ///     output.write(&[0x01, 0x02]),
+    /// );
+    /// # })
+    /// ```
+    pub fn split(&self, in_endpoint: u8, out_endpoint: u8) -> (InEndpointHandle, OutEndpointHandle) {
+        let input = InEndpointHandle {
+            interface: self.clone(),
+            endpoint: in_endpoint,
+        };
+
+        let output = OutEndpointHandle {
+            interface: self.clone(),
+            endpoint: out_endpoint,
+        };
+
+        (input, output)
+    }
+
+    /// The largest packet the endpoint with the address `endpoint` sends or
+    /// receives (`wMaxPacketSize`), in the selected alternate setting.
+    ///
+    /// Fails with [`Error::EndpointNotFound`] if this interface has no such
+    /// endpoint.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// let interface = device.open_interface(0).await.expect("Failed to open interface");
+    ///
+    /// // Read whole packets at a time
+    /// let packet_size = interface.max_packet_size(0x81).expect("No such endpoint");
+    /// let data = interface.bulk_in(0x81, packet_size * 8).await;
+    /// # })
+    /// ```
+    pub fn max_packet_size(&self, endpoint: u8) -> Result<usize, Error> {
+        self.endpoints()
+            .into_iter()
+            .find(|info| info.address == endpoint)
+            .map(|info| info.max_packet_size)
+            .ok_or(Error::EndpointNotFound(endpoint))
+    }
+}
+
+/// A handle which can only read from one bulk IN endpoint, created with
+/// [`Interface::split`].
+#[derive(Debug, Clone)]
+pub struct InEndpointHandle {
+    interface: Interface,
+    endpoint: u8,
+}
+
+impl InEndpointHandle {
+    /// The address of the endpoint this handle reads from.
+    pub fn endpoint(&self) -> u8 {
+        self.endpoint
+    }
+
+    /// Read up to `length` bytes, as with [`UsbInterface::bulk_in`].
+    pub async fn read(&self, length: usize) -> Result<Vec<u8>, Error> {
+        self.interface.bulk_in(self.endpoint, length).await
+    }
+
+    /// Read up to `length` bytes, as with [`UsbInterface::bulk_in_result`].
+    pub async fn read_result(&self, length: usize) -> Result<TransferResult, Error> {
+        self.interface.bulk_in_result(self.endpoint, length).await
+    }
+
+    /// Read up to `length` bytes, as with [`UsbInterface::bulk_in_timeout`].
+    pub async fn read_timeout(&self, length: usize, timeout: Duration) -> Result<Vec<u8>, Error> {
+        self.interface.bulk_in_timeout(self.endpoint, length, timeout).await
+    }
+
+    /// Clear a stall on the endpoint, as with [`UsbInterface::clear_halt`].
+    pub async fn clear_halt(&self) -> Result<(), Error> {
+        self.interface.clear_halt(self.endpoint).await
+    }
+
+    /// Turn this handle into a [`BulkReader`], requesting `transfer_size`
+    /// bytes at a time.
+    pub fn into_reader(self, transfer_size: usize) -> BulkReader {
+        self.interface.reader(self.endpoint, transfer_size)
+    }
+}
+
+/// A handle which can only write to one bulk OUT endpoint, created with
+/// [`Interface::split`].
+#[derive(Debug, Clone)]
+pub struct OutEndpointHandle {
+    interface: Interface,
+    endpoint: u8,
+}
+
+impl OutEndpointHandle {
+    /// The address of the endpoint this handle writes to.
+    pub fn endpoint(&self) -> u8 {
+        self.endpoint
+    }
+
+    /// Write `data`, as with [`UsbInterface::bulk_out`].
+    pub async fn write(&self, data: &[u8]) -> Result<usize, Error> {
+        self.interface.bulk_out(self.endpoint, data).await
+    }
+
+    /// Write `data`, as with [`UsbInterface::bulk_out_timeout`].
+    pub async fn write_timeout(&self, data: &[u8], timeout: Duration) -> Result<usize, Error> {
+        self.interface.bulk_out_timeout(self.endpoint, data, timeout).await
+    }
+
+    /// Clear a stall on the endpoint, as with [`UsbInterface::clear_halt`].
+    pub async fn clear_halt(&self) -> Result<(), Error> {
+        self.interface.clear_halt(self.endpoint).await
+    }
+
+    /// Turn this handle into a [`BulkWriter`], sending at most
+    /// `transfer_size` bytes per transfer.
+    pub fn into_writer(self, transfer_size: usize) -> BulkWriter {
+        self.interface.writer(self.endpoint, transfer_size)
+    }
+
+    /// Turn this handle into a [`BulkSink`].
+    pub fn into_sink(self) -> BulkSink {
+        self.interface.sink(self.endpoint)
+    }
+}
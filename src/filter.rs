@@ -0,0 +1,777 @@
+//! Matching devices against a [`DeviceFilter`], the same way on every
+//! backend.
+
+use std::cmp::Reverse;
+use std::str::FromStr;
+
+use crate::usb::{
+    DeviceInfoSnapshot, Error, GetDeviceOptions, InterfaceFilterInfo, ParseFilterError, UsbDeviceInfo,
+    Version,
+};
+use crate::{DeviceFilter, DeviceInfo};
+
+impl DeviceFilter {
+    /// Start building a filter, setting only the fields which are needed.
+    ///
+    /// ```
+    /// use cross_usb::{device_filter, DeviceFilter};
+    ///
+    /// let filter = DeviceFilter::builder().vendor_id(0x054c).product_id(0x00c9).build();
+    /// assert!(filter == device_filter!{vendor_id: 0x054c, product_id: 0x00c9});
+    ///
+    /// // Filters can be built up from settings which are only known at runtime
+    /// let serial_number: Option<&str> = Some("4A3F9C01");
+    /// let mut builder = DeviceFilter::builder().vendor_id(0x054c);
+    /// if let Some(serial_number) = serial_number {
+    ///     builder = builder.serial_number(serial_number);
+    /// }
+    ///
+    /// assert!(builder.build() == device_filter!{vendor_id: 0x054c, serial_number: "4A3F9C01"});
+    /// ```
+    pub fn builder() -> DeviceFilterBuilder {
+        DeviceFilterBuilder {
+            filter: DeviceFilter::default(),
+        }
+    }
+
+    /// Whether `device_info` matches this filter, as it would in
+    /// [`crate::get_device`] and [`crate::get_device_list`]. See
+    /// [`DeviceFilter::matches_info`] for how the fields are matched.
+    ///
+    /// This doesn't depend on [`DeviceFilter::exclude`], which only decides
+    /// what happens to matching devices when finding devices.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// use cross_usb::prelude::*;
+    /// use cross_usb::device_filter;
+    ///
+    /// let filter = device_filter!{vendor_id: 0x054c, product_id: 0x00c9};
+    ///
+    /// // Look for matching devices which were plugged in since the last check
+    /// for device_info in cross_usb::list_devices().await.expect("Failed to list devices") {
+    ///     if filter.matches(&device_info).await {
+    ///         let device = device_info.open().await;
+    ///     }
+    /// }
+    /// # })
+    /// ```
+    pub async fn matches(&self, device_info: &DeviceInfo) -> bool {
+        let info = device_info.info().await;
+        let interfaces = device_info.interfaces().await;
+
+        self.matches_info(&info, &interfaces)
+    }
+
+    /// Whether `device`, which has the interfaces `interfaces`, matches this
+    /// filter. Every field which is set must match, and a filter with no
+    /// fields set matches nothing.
+    ///
+    /// As in WebUSB, the class, subclass and protocol match if they match the
+    /// device itself or any one of its interfaces, so composite devices can
+    /// be found by the class of one of their functions.
+    ///
+    /// ```
+    /// use cross_usb::device_filter;
+    /// use cross_usb::usb::{DeviceInfoSnapshot, InterfaceFilterInfo, Version};
+    ///
+    /// // A HID device from vendor 0x046d
+    /// let device = DeviceInfoSnapshot {
+    ///     vendor_id: 0x046d,
+    ///     product_id: 0xc52b,
+    ///     class: 0x03,
+    ///     subclass: 0x01,
+    ///     protocol: 0x02,
+    ///     device_version: Version::new(1, 2, 0),
+    ///     manufacturer_string: Some(String::from("Logitech")),
+    ///     product_string: Some(String::from("USB Receiver")),
+    ///     serial_number_string: Some(String::from("4A3F9C01")),
+    ///     bus_number: Some(3),
+    ///     device_address: Some(7),
+    ///     port_chain: Some(vec![1, 4]),
+    /// };
+    ///
+    /// assert!(device_filter!{vendor_id: 0x046d}.matches_info(&device, &[]));
+    /// assert!(device_filter!{vendor_id: 0x046d, product_id: 0xc52b}.matches_info(&device, &[]));
+    /// assert!(device_filter!{vendor_id: 0x046d, class: 0x03, protocol: 0x02}.matches_info(&device, &[]));
+    ///
+    /// // Only some of the fields match
+    /// assert!(!device_filter!{vendor_id: 0x054c, product_id: 0xc52b}.matches_info(&device, &[]));
+    /// assert!(!device_filter!{vendor_id: 0x046d, product_id: 0x00c9}.matches_info(&device, &[]));
+    /// assert!(!device_filter!{vendor_id: 0x054c, class: 0x03}.matches_info(&device, &[]));
+    /// assert!(!device_filter!{class: 0x03, subclass: 0x00}.matches_info(&device, &[]));
+    ///
+    /// // Serial numbers must match exactly
+    /// assert!(device_filter!{vendor_id: 0x046d, serial_number: "4A3F9C01"}.matches_info(&device, &[]));
+    /// assert!(!device_filter!{vendor_id: 0x046d, serial_number: "4a3f9c01"}.matches_info(&device, &[]));
+    ///
+    /// // Strings only need to contain the pattern, in any case
+    /// assert!(device_filter!{product_string_contains: "receiver"}.matches_info(&device, &[]));
+    /// assert!(!device_filter!{vendor_id: 0x046d, product_string_contains: "Mouse"}.matches_info(&device, &[]));
+    /// assert!(device_filter!{manufacturer_string_contains: "LOGI"}.matches_info(&device, &[]));
+    ///
+    /// // Release numbers are compared as versions, including both ends of the range
+    /// assert!(Version::from_bcd(0x0110) < Version::from_bcd(0x0200));
+    /// let rev_a = device_filter!{vendor_id: 0x046d, device_version_max: Version::from_bcd(0x0120)};
+    /// let rev_b = device_filter!{vendor_id: 0x046d, device_version_min: Version::from_bcd(0x0200)};
+    /// assert!(rev_a.matches_info(&device, &[]));
+    /// assert!(!rev_b.matches_info(&device, &[]));
+    /// assert!(device_filter!{
+    ///     device_version_min: Version::from_bcd(0x0110),
+    ///     device_version_max: Version::from_bcd(0x0120),
+    /// }.matches_info(&device, &[]));
+    /// assert!(!device_filter!{device_version_min: Version::from_bcd(0x0121)}.matches_info(&device, &[]));
+    ///
+    /// // Where the device is plugged in
+    /// assert!(device_filter!{vendor_id: 0x046d, bus_number: 3, port_chain: vec![1, 4]}.matches_info(&device, &[]));
+    /// assert!(!device_filter!{vendor_id: 0x046d, bus_number: 3, port_chain: vec![1]}.matches_info(&device, &[]));
+    /// assert!(!device_filter!{bus_number: 2}.matches_info(&device, &[]));
+    ///
+    /// // Nothing is set
+    /// assert!(!cross_usb::DeviceFilter::default().matches_info(&device, &[]));
+    /// assert!(!device_filter!{exclude: true}.matches_info(&device, &[]));
+    ///
+    /// // A composite device with a CDC serial port and a vendor interface
+    /// let composite = DeviceInfoSnapshot { class: 0x00, subclass: 0x00, protocol: 0x00, ..device };
+    /// let interfaces = [
+    ///     InterfaceFilterInfo { number: 0, class: 0x02, subclass: 0x02, protocol: 0x01, interface_string: None },
+    ///     InterfaceFilterInfo { number: 1, class: 0x0A, subclass: 0x00, protocol: 0x00, interface_string: None },
+    ///     InterfaceFilterInfo { number: 2, class: 0xFF, subclass: 0x42, protocol: 0x01, interface_string: None },
+    /// ];
+    ///
+    /// assert!(device_filter!{class: 0x0A}.matches_info(&composite, &interfaces));
+    /// assert!(device_filter!{vendor_id: 0x046d, class: 0xFF, subclass: 0x42}.matches_info(&composite, &interfaces));
+    ///
+    /// // The codes have to match the same interface
+    /// assert!(!device_filter!{class: 0xFF, subclass: 0x02}.matches_info(&composite, &interfaces));
+    /// assert!(!device_filter!{vendor_id: 0x054c, class: 0x0A}.matches_info(&composite, &interfaces));
+    /// ```
+    pub fn matches_info(&self, device: &DeviceInfoSnapshot, interfaces: &[InterfaceFilterInfo]) -> bool {
+        if self.specificity() == 0 {
+            return false;
+        }
+
+        let codes_match = self.codes_match(device.class, device.subclass, device.protocol)
+            || interfaces
+                .iter()
+                .any(|interface| self.codes_match(interface.class, interface.subclass, interface.protocol));
+
+        // Where a device is plugged in can't be filtered on in WASM
+        #[cfg(not(target_family = "wasm"))]
+        let location_matches = self
+            .bus_number
+            .is_none_or(|bus_number| device.bus_number == Some(bus_number))
+            && self
+                .port_chain
+                .as_ref()
+                .is_none_or(|port_chain| device.port_chain.as_ref() == Some(port_chain));
+        #[cfg(target_family = "wasm")]
+        let location_matches = true;
+
+        codes_match
+            && location_matches
+            && self.vendor_id.is_none_or(|vendor_id| vendor_id == device.vendor_id)
+            && self.product_id.is_none_or(|product_id| product_id == device.product_id)
+            && self
+                .serial_number
+                .as_ref()
+                .is_none_or(|serial| device.serial_number_string.as_ref() == Some(serial))
+            && contains(&self.manufacturer_string_contains, &device.manufacturer_string)
+            && contains(&self.product_string_contains, &device.product_string)
+            && self.device_version_min.is_none_or(|min| device.device_version >= min)
+            && self.device_version_max.is_none_or(|max| device.device_version <= max)
+    }
+
+    /// The number of fields this filter sets. When devices match different
+    /// filters, [`crate::get_device`] picks the one matching the filter
+    /// which sets the most fields.
+    ///
+    /// ```
+    /// use cross_usb::device_filter;
+    ///
+    /// assert_eq!(device_filter!{vendor_id: 0x054c, product_id: 0x00c9}.specificity(), 2);
+    /// assert_eq!(device_filter!{vendor_id: 0x054c}.specificity(), 1);
+    /// assert_eq!(cross_usb::DeviceFilter::default().specificity(), 0);
+    /// assert_eq!(device_filter!{vendor_id: 0x054c, exclude: true}.specificity(), 1);
+    /// ```
+    pub fn specificity(&self) -> usize {
+        let fields = [
+            self.vendor_id.is_some(),
+            self.product_id.is_some(),
+            self.class.is_some(),
+            self.subclass.is_some(),
+            self.protocol.is_some(),
+            self.serial_number.is_some(),
+            self.manufacturer_string_contains.is_some(),
+            self.product_string_contains.is_some(),
+            self.device_version_min.is_some(),
+            self.device_version_max.is_some(),
+            #[cfg(not(target_family = "wasm"))]
+            self.bus_number.is_some(),
+            #[cfg(not(target_family = "wasm"))]
+            self.port_chain.is_some(),
+        ];
+
+        fields.into_iter().filter(|set| *set).count()
+    }
+
+    /// Whether the class, subclass and protocol of one device or interface
+    /// match the ones this filter sets.
+    fn codes_match(&self, class: u8, subclass: u8, protocol: u8) -> bool {
+        self.class.is_none_or(|filter| filter == class)
+            && self.subclass.is_none_or(|filter| filter == subclass)
+            && self.protocol.is_none_or(|filter| filter == protocol)
+    }
+}
+
+impl FromStr for DeviceFilter {
+    type Err = ParseFilterError;
+
+    /// Parse a filter in the `VID:PID` form used by tools such as `lsusb`,
+    /// where the IDs are hexadecimal with or without a `0x` prefix. Either ID
+    /// may be left out, as in `054c` or `054c:`, and a serial number can
+    /// follow as in `VID:PID:serial`.
+    ///
+    /// ```
+    /// use cross_usb::{device_filter, DeviceFilter};
+    /// use cross_usb::usb::ParseFilterError;
+    ///
+    /// let parse = |s: &str| s.parse::<DeviceFilter>();
+    ///
+    /// assert!(parse("054c:00c9").unwrap() == device_filter!{vendor_id: 0x054c, product_id: 0x00c9});
+    /// assert!(parse("0x054C:0x00C9\n").unwrap() == device_filter!{vendor_id: 0x054c, product_id: 0x00c9});
+    /// assert!(parse("054c").unwrap() == device_filter!{vendor_id: 0x054c});
+    /// assert!(parse("054c:").unwrap() == device_filter!{vendor_id: 0x054c});
+    /// assert!(parse(":00c9").unwrap() == device_filter!{product_id: 0x00c9});
+    /// assert!(
+    ///     parse("054c:00c9:PROD-0001").unwrap()
+    ///         == device_filter!{vendor_id: 0x054c, product_id: 0x00c9, serial_number: "PROD-0001"}
+    /// );
+    ///
+    /// // Serial numbers may contain colons
+    /// assert!(parse("054c::a:b").unwrap() == device_filter!{vendor_id: 0x054c, serial_number: "a:b"});
+    ///
+    /// assert_eq!(parse(""), Err(ParseFilterError::Empty));
+    /// assert_eq!(parse(" : "), Err(ParseFilterError::Empty));
+    /// assert_eq!(parse("sony"), Err(ParseFilterError::InvalidVendorId(String::from("sony"))));
+    /// assert_eq!(parse("054c:100c9"), Err(ParseFilterError::InvalidProductId(String::from("100c9"))));
+    /// assert_eq!(parse("054c:+0c9"), Err(ParseFilterError::InvalidProductId(String::from("+0c9"))));
+    /// assert_eq!(parse("054c:00c9:"), Err(ParseFilterError::EmptySerialNumber));
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, ':');
+
+        let vendor_id = parse_id(parts.next().unwrap_or_default())
+            .map_err(ParseFilterError::InvalidVendorId)?;
+        let product_id = parse_id(parts.next().unwrap_or_default())
+            .map_err(ParseFilterError::InvalidProductId)?;
+        let serial_number = match parts.next() {
+            Some("") => return Err(ParseFilterError::EmptySerialNumber),
+            serial_number => serial_number.map(String::from),
+        };
+
+        if vendor_id.is_none() && product_id.is_none() {
+            return Err(ParseFilterError::Empty);
+        }
+
+        Ok(DeviceFilter {
+            vendor_id,
+            product_id,
+            serial_number,
+            ..DeviceFilter::default()
+        })
+    }
+}
+
+/// Parse a hexadecimal ID, which is `None` if it's empty. The error is the
+/// text which couldn't be parsed.
+fn parse_id(id: &str) -> Result<Option<u16>, String> {
+    let id = id.trim();
+    if id.is_empty() {
+        return Ok(None);
+    }
+
+    let digits = id
+        .strip_prefix("0x")
+        .or_else(|| id.strip_prefix("0X"))
+        .unwrap_or(id);
+
+    // `from_str_radix` accepts a sign, which isn't part of an ID
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(id.to_string());
+    }
+
+    u16::from_str_radix(digits, 16).map(Some).map_err(|_| id.to_string())
+}
+
+/// A builder for a [`DeviceFilter`], from [`DeviceFilter::builder`]. Fields
+/// which aren't set match any device.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceFilterBuilder {
+    filter: DeviceFilter,
+}
+
+impl DeviceFilterBuilder {
+    /// Match devices with this vendor ID.
+    pub fn vendor_id(mut self, vendor_id: u16) -> Self {
+        self.filter.vendor_id = Some(vendor_id);
+        self
+    }
+
+    /// Match devices with this product ID.
+    pub fn product_id(mut self, product_id: u16) -> Self {
+        self.filter.product_id = Some(product_id);
+        self
+    }
+
+    /// Match devices, or interfaces of devices, with this class code.
+    pub fn class(mut self, class: u8) -> Self {
+        self.filter.class = Some(class);
+        self
+    }
+
+    /// Match devices, or interfaces of devices, with this subclass code.
+    pub fn subclass(mut self, subclass: u8) -> Self {
+        self.filter.subclass = Some(subclass);
+        self
+    }
+
+    /// Match devices, or interfaces of devices, with this protocol code.
+    pub fn protocol(mut self, protocol: u8) -> Self {
+        self.filter.protocol = Some(protocol);
+        self
+    }
+
+    /// Match devices with exactly this serial number.
+    pub fn serial_number(mut self, serial_number: impl Into<String>) -> Self {
+        self.filter.serial_number = Some(serial_number.into());
+        self
+    }
+
+    /// Match devices whose manufacturer string contains `pattern`, ignoring
+    /// case.
+    pub fn manufacturer_string_contains(mut self, pattern: impl Into<String>) -> Self {
+        self.filter.manufacturer_string_contains = Some(pattern.into());
+        self
+    }
+
+    /// Match devices whose product string contains `pattern`, ignoring case.
+    pub fn product_string_contains(mut self, pattern: impl Into<String>) -> Self {
+        self.filter.product_string_contains = Some(pattern.into());
+        self
+    }
+
+    /// Match devices with this release number (`bcdDevice`) or a later one.
+    pub fn device_version_min(mut self, version: Version) -> Self {
+        self.filter.device_version_min = Some(version);
+        self
+    }
+
+    /// Match devices with this release number (`bcdDevice`) or an earlier
+    /// one.
+    pub fn device_version_max(mut self, version: Version) -> Self {
+        self.filter.device_version_max = Some(version);
+        self
+    }
+
+    /// Match devices on this bus.
+    ///
+    /// **Note:** This is only available on native.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn bus_number(mut self, bus_number: u8) -> Self {
+        self.filter.bus_number = Some(bus_number);
+        self
+    }
+
+    /// Match devices plugged in through these ports.
+    ///
+    /// **Note:** This is only available on native.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn port_chain(mut self, port_chain: impl Into<Vec<u8>>) -> Self {
+        self.filter.port_chain = Some(port_chain.into());
+        self
+    }
+
+    /// Exclude the devices this filter matches instead of including them.
+    pub fn exclude(mut self, exclude: bool) -> Self {
+        self.filter.exclude = exclude;
+        self
+    }
+
+    /// Finish building the filter.
+    pub fn build(self) -> DeviceFilter {
+        self.filter
+    }
+}
+
+/// How well `device_info` matches `device_filters`, or `None` if it matches
+/// none of the filters which include devices, or any which exclude them.
+/// Lower is better: the most specific filter it matches, then the earliest
+/// one in the list.
+pub(crate) async fn rank(
+    device_filters: &[DeviceFilter],
+    device_info: &DeviceInfo,
+) -> Option<(Reverse<usize>, usize)> {
+    let info = device_info.info().await;
+    let interfaces = device_info.interfaces().await;
+
+    rank_info(device_filters, &info, &interfaces)
+}
+
+/// The device in `devices` matching the most specific of `device_filters`,
+/// which is the first one if several match equally well.
+pub(crate) async fn best(device_filters: &[DeviceFilter], devices: Vec<DeviceInfo>) -> Option<DeviceInfo> {
+    let mut ranked = Vec::new();
+    for device_info in devices {
+        ranked.push((rank(device_filters, &device_info).await, device_info));
+    }
+
+    best_ranked(ranked)
+}
+
+/// The device with the best rank, which is the first one if several are
+/// ranked equally. Devices without a rank don't match.
+fn best_ranked<T>(devices: impl IntoIterator<Item = (Option<(Reverse<usize>, usize)>, T)>) -> Option<T> {
+    let mut best = None;
+    for (rank, device) in devices {
+        let Some(rank) = rank else {
+            continue;
+        };
+
+        if best.as_ref().is_none_or(|(best_rank, _)| rank < *best_rank) {
+            best = Some((rank, device));
+        }
+    }
+
+    best.map(|(_, device)| device)
+}
+
+fn rank_info(
+    device_filters: &[DeviceFilter],
+    info: &DeviceInfoSnapshot,
+    interfaces: &[InterfaceFilterInfo],
+) -> Option<(Reverse<usize>, usize)> {
+    if excluded(device_filters, info, interfaces) {
+        return None;
+    }
+
+    device_filters
+        .iter()
+        .enumerate()
+        .filter(|(_, filter)| !filter.exclude && filter.matches_info(info, interfaces))
+        .map(|(index, filter)| (Reverse(filter.specificity()), index))
+        .min()
+}
+
+/// Whether a device matches any of the filters which exclude devices.
+fn excluded(device_filters: &[DeviceFilter], info: &DeviceInfoSnapshot, interfaces: &[InterfaceFilterInfo]) -> bool {
+    device_filters
+        .iter()
+        .any(|filter| filter.exclude && filter.matches_info(info, interfaces))
+}
+
+/// Whether `string` contains `pattern` ignoring case, if a pattern is set.
+/// Devices without the string never match a pattern.
+fn contains(pattern: &Option<String>, string: &Option<String>) -> bool {
+    let Some(pattern) = pattern else {
+        return true;
+    };
+
+    string
+        .as_ref()
+        .is_some_and(|string| string.to_lowercase().contains(&pattern.to_lowercase()))
+}
+
+/// List the devices matching any of `device_filters` which can be used
+/// without asking the user, never showing the browser's device chooser.
+///
+/// Unlike [`crate::get_device_list`], this returns an empty list instead of
+/// failing when no device matches, so the user can be asked for a device
+/// only then. As everywhere else, an empty list of filters matches no
+/// devices.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// assert!(cross_usb::get_paired_devices(vec![]).await.unwrap().is_empty());
+/// # })
+/// ```
+///
+/// **Note:** On WASM, these are the devices the user has already given the
+/// page access to. On native, these are all the devices connected to the
+/// system.
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::device_filter;
+///
+/// let filter = vec![device_filter!{vendor_id: 0x054c, product_id: 0x00c9}];
+///
+/// // Reconnect without asking if possible
+/// let paired = cross_usb::get_paired_devices(filter.clone()).await.expect("Failed to list devices");
+/// let device_info = match paired.into_iter().next() {
+///     Some(device_info) => device_info,
+///     None => cross_usb::get_device(filter).await.expect("No device was picked"),
+/// };
+/// # })
+/// ```
+pub async fn get_paired_devices(device_filters: Vec<DeviceFilter>) -> Result<Vec<DeviceInfo>, Error> {
+    if !includes_any(&device_filters) {
+        return Ok(Vec::new());
+    }
+
+    let mut devices = Vec::new();
+    for device_info in crate::context::list_devices().await? {
+        if matches_any(&device_filters, &device_info).await {
+            devices.push(device_info);
+        }
+    }
+
+    Ok(devices)
+}
+
+/// A device, along with the filter it was found with.
+#[derive(Debug)]
+pub struct MatchedDevice {
+    /// The device which was found.
+    pub info: DeviceInfo,
+
+    /// The index in the list of filters of the filter the device matched.
+    /// When it matches several, this is the one it was ranked by: the most
+    /// specific, then the earliest in the list.
+    pub filter_index: usize,
+}
+
+/// Gets a single device as with [`crate::get_device`], along with the index
+/// of the filter it matched, to tell which of several kinds of device was
+/// found.
+///
+/// **Note:** On WASM, the device the user picks from the browser's chooser
+/// is rejected with [`Error::DeviceNotFound`] if it doesn't match any of the
+/// filters, since the browser can't match every field.
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::device_filter;
+///
+/// let filter = vec![
+///     device_filter!{vendor_id: 0x054c, product_id: 0x00c9},
+///     device_filter!{vendor_id: 0x054c, product_id: 0x0186},
+/// ];
+///
+/// let device = cross_usb::get_device_matched(filter).await.expect("Could not find device");
+/// match device.filter_index {
+///     0 => { /* The first kind of device */ }
+///     _ => { /* The second kind of device */ }
+/// }
+/// # })
+/// ```
+pub async fn get_device_matched(device_filters: Vec<DeviceFilter>) -> Result<MatchedDevice, Error> {
+    let device_info =
+        crate::context::get_device_with_options(device_filters.clone(), GetDeviceOptions::default()).await?;
+
+    match rank(&device_filters, &device_info).await {
+        Some((_, filter_index)) => Ok(MatchedDevice {
+            info: device_info,
+            filter_index,
+        }),
+        None => Err(Error::DeviceNotFound),
+    }
+}
+
+/// Gets a list of devices as with [`crate::get_device_list`], along with the
+/// index of the filter each of them matched.
+pub async fn get_device_list_matched(device_filters: Vec<DeviceFilter>) -> Result<Vec<MatchedDevice>, Error> {
+    let mut devices = Vec::new();
+    for device_info in crate::context::get_device_list(device_filters.clone()).await? {
+        if let Some((_, filter_index)) = rank(&device_filters, &device_info).await {
+            devices.push(MatchedDevice {
+                info: device_info,
+                filter_index,
+            });
+        }
+    }
+
+    Ok(devices)
+}
+
+/// Whether any of `device_filters` include devices. Without one no device
+/// can match, and browsers refuse to show the device chooser.
+pub(crate) fn includes_any(device_filters: &[DeviceFilter]) -> bool {
+    device_filters.iter().any(|filter| !filter.exclude)
+}
+
+/// Whether `device_info` matches any of the filters in `device_filters`
+/// which include devices, and none which exclude them.
+pub(crate) async fn matches_any(device_filters: &[DeviceFilter], device_info: &DeviceInfo) -> bool {
+    rank(device_filters, device_info).await.is_some()
+}
+
+/// Whether `device_info` matches any of `device_filters`, or none of them
+/// include devices, without matching one which excludes it, and `predicate`
+/// accepts it.
+pub(crate) async fn accepts(
+    device_filters: &[DeviceFilter],
+    device_info: &DeviceInfo,
+    predicate: &impl Fn(&DeviceInfoSnapshot) -> bool,
+) -> bool {
+    let info = device_info.info().await;
+    let interfaces = device_info.interfaces().await;
+
+    let filtered = if device_filters.iter().all(|filter| filter.exclude) {
+        !excluded(device_filters, &info, &interfaces)
+    } else {
+        rank_info(device_filters, &info, &interfaces).is_some()
+    };
+
+    filtered && predicate(&info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device_filter;
+
+    fn device(vendor_id: u16, product_id: u16, class: u8, serial_number: Option<&str>) -> DeviceInfoSnapshot {
+        DeviceInfoSnapshot {
+            vendor_id,
+            product_id,
+            class,
+            subclass: 0x00,
+            protocol: 0x00,
+            device_version: Version::new(1, 0, 0),
+            manufacturer_string: Some(String::from("Sony")),
+            product_string: Some(String::from("Memory Card Adaptor")),
+            serial_number_string: serial_number.map(String::from),
+            bus_number: Some(1),
+            device_address: Some(4),
+            port_chain: Some(vec![2]),
+        }
+    }
+
+    fn interface(number: u8, class: u8) -> InterfaceFilterInfo {
+        InterfaceFilterInfo {
+            number,
+            class,
+            subclass: 0x00,
+            protocol: 0x00,
+            interface_string: None,
+        }
+    }
+
+    #[test]
+    fn matches_agrees_with_finding_devices() {
+        let devices = [
+            (device(0x054c, 0x00c9, 0x00, Some("A1")), vec![interface(0, 0xFF)]),
+            (device(0x054c, 0x0186, 0x00, None), vec![interface(0, 0x08)]),
+            (device(0x046d, 0xc52b, 0x03, Some("B2")), vec![]),
+            (device(0x046d, 0x0825, 0xEF, None), vec![interface(0, 0x0E), interface(1, 0x01)]),
+        ];
+
+        let filters = [
+            DeviceFilter::default(),
+            device_filter!{vendor_id: 0x054c},
+            device_filter!{vendor_id: 0x054c, product_id: 0x00c9},
+            device_filter!{vendor_id: 0x054c, serial_number: "A1"},
+            device_filter!{class: 0x03},
+            device_filter!{class: 0x08},
+            device_filter!{class: 0x01, subclass: 0x00},
+            device_filter!{product_string_contains: "card"},
+            device_filter!{manufacturer_string_contains: "logi"},
+            device_filter!{device_version_min: Version::new(1, 0, 0), device_version_max: Version::new(1, 0, 0)},
+            device_filter!{device_version_min: Version::new(2, 0, 0)},
+        ];
+
+        for (info, interfaces) in &devices {
+            for filter in &filters {
+                assert_eq!(
+                    rank_info(std::slice::from_ref(filter), info, interfaces).is_some(),
+                    filter.matches_info(info, interfaces),
+                    "{filter:?} on {info:?}",
+                );
+            }
+
+            // However many filters are given, a device is found if any of
+            // them matches it
+            assert_eq!(
+                rank_info(&filters, info, interfaces).is_some(),
+                filters.iter().any(|filter| filter.matches_info(info, interfaces)),
+                "{info:?}",
+            );
+        }
+    }
+
+    // The device `best` picks from `devices`, by its index
+    fn best_index(device_filters: &[DeviceFilter], devices: &[DeviceInfoSnapshot]) -> Option<usize> {
+        best_ranked(
+            devices
+                .iter()
+                .enumerate()
+                .map(|(index, info)| (rank_info(device_filters, info, &[]), index)),
+        )
+    }
+
+    #[test]
+    fn best_prefers_the_most_specific_filter() {
+        let devices = [
+            device(0x054c, 0x0186, 0x00, None),
+            device(0x054c, 0x00c9, 0x00, None),
+        ];
+
+        // The second device matches a filter with more fields set, even
+        // though it comes later in both lists
+        let filters = [
+            device_filter!{vendor_id: 0x054c},
+            device_filter!{vendor_id: 0x054c, product_id: 0x00c9},
+        ];
+        assert_eq!(best_index(&filters, &devices), Some(1));
+        assert_eq!(rank_info(&filters, &devices[1], &[]), Some((Reverse(2), 1)));
+    }
+
+    #[test]
+    fn best_breaks_ties_by_filter_order() {
+        let devices = [
+            device(0x054c, 0x0186, 0x00, None),
+            device(0x054c, 0x00c9, 0x00, None),
+        ];
+
+        let filters = [
+            device_filter!{vendor_id: 0x054c, product_id: 0x00c9},
+            device_filter!{vendor_id: 0x054c, product_id: 0x0186},
+        ];
+        assert_eq!(best_index(&filters, &devices), Some(1));
+
+        // Devices matching the same filter are picked in the order they're
+        // listed in
+        let devices = [
+            device(0x054c, 0x00c9, 0x00, Some("A1")),
+            device(0x054c, 0x00c9, 0x00, Some("B2")),
+        ];
+        assert_eq!(best_index(&filters, &devices), Some(0));
+    }
+
+    #[test]
+    fn best_skips_excluded_devices() {
+        let devices = [
+            device(0x054c, 0x00c9, 0x00, Some("A1")),
+            device(0x054c, 0x00c9, 0x00, Some("B2")),
+        ];
+
+        // The exclude filter wins, even over a more specific include filter
+        let filters = [
+            device_filter!{vendor_id: 0x054c, product_id: 0x00c9, serial_number: "A1"},
+            device_filter!{vendor_id: 0x054c},
+            device_filter!{serial_number: "A1", exclude: true},
+        ];
+        assert_eq!(rank_info(&filters, &devices[0], &[]), None);
+        assert_eq!(best_index(&filters, &devices), Some(1));
+
+        let filters = [
+            device_filter!{vendor_id: 0x054c},
+            device_filter!{vendor_id: 0x054c, exclude: true},
+        ];
+        assert_eq!(best_index(&filters, &devices), None);
+    }
+}
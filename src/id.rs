@@ -0,0 +1,242 @@
+//! Identifying devices, and finding a device again from a [`DeviceId`],
+//! which can be stored between runs of a program.
+
+use crate::usb::{DeviceId, DeviceInfoSnapshot, Error, GetDeviceOptions, UsbDevice, UsbDeviceInfo};
+use crate::{filter, Device, DeviceFilter, DeviceInfo};
+
+impl DeviceInfo {
+    /// A [`DeviceId`] which identifies this device, so that it can be opened
+    /// again later with [`crate::open_by_id`], or found again with
+    /// [`crate::rebind`].
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device_info = cross_usb::get_device(vec![]).await.expect("Failed to find device");
+    /// let id = device_info.id().await;
+    ///
+    /// // Some time later...
+    /// let device = cross_usb::open_by_id(&id).await.expect("Failed to open device");
+    /// # })
+    /// ```
+    pub async fn id(&self) -> DeviceId {
+        DeviceId {
+            vendor_id: self.vendor_id().await,
+            product_id: self.product_id().await,
+            serial_number: self.serial_number_string().await,
+            bus_number: self.bus_number().await,
+            port_chain: self.port_chain().await,
+        }
+    }
+
+    /// An owned copy of the identifying information of this device, as with
+    /// [`Device::info`].
+    pub async fn info(&self) -> DeviceInfoSnapshot {
+        DeviceInfoSnapshot {
+            vendor_id: self.vendor_id().await,
+            product_id: self.product_id().await,
+            class: self.class().await,
+            subclass: self.subclass().await,
+            protocol: self.protocol().await,
+            device_version: self.device_version().await,
+            manufacturer_string: self.manufacturer_string().await,
+            product_string: self.product_string().await,
+            serial_number_string: self.serial_number_string().await,
+            bus_number: self.bus_number().await,
+            device_address: self.device_address().await,
+            port_chain: self.port_chain().await,
+        }
+    }
+}
+
+impl Device {
+    /// An owned copy of the identifying information of this device, for
+    /// logging or showing to the user.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// let info = device.info().await;
+    /// println!("Opened {:04x}:{:04x} {:?}", info.vendor_id, info.product_id, info.product_string);
+    /// # })
+    /// ```
+    pub async fn info(&self) -> DeviceInfoSnapshot {
+        DeviceInfoSnapshot {
+            vendor_id: self.vendor_id().await,
+            product_id: self.product_id().await,
+            class: self.class().await,
+            subclass: self.subclass().await,
+            protocol: self.protocol().await,
+            device_version: self.device_version().await,
+            manufacturer_string: self.manufacturer_string().await,
+            product_string: self.product_string().await,
+            serial_number_string: self.serial_number_string().await,
+            bus_number: self.bus_number().await,
+            device_address: self.device_address().await,
+            port_chain: self.port_chain().await,
+        }
+    }
+}
+
+impl DeviceId {
+    /// Whether `other` is the same physical device. Devices with a serial
+    /// number are matched by it, so they can be found on any port. Devices
+    /// without one are matched by the port they are connected to, where the
+    /// platform reports it.
+    pub(crate) fn matches(&self, other: &DeviceId) -> bool {
+        if self.vendor_id != other.vendor_id || self.product_id != other.product_id {
+            return false;
+        }
+
+        if self.serial_number.is_some() {
+            return self.serial_number == other.serial_number;
+        }
+
+        unknown_or_equal(&self.bus_number, &other.bus_number)
+            && unknown_or_equal(&self.port_chain, &other.port_chain)
+    }
+}
+
+fn unknown_or_equal<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> bool {
+    a.is_none() || b.is_none() || a == b
+}
+
+/// Find and open the device identified by `id`, from [`DeviceInfo::id`].
+///
+/// Fails with [`Error::DeviceNotFound`] if no device matches, or
+/// [`Error::AmbiguousDevice`] if more than one does.
+///
+/// **Note:** On WASM, only devices the page has already been given access to
+/// are searched, so the user isn't asked to pick the device again.
+pub async fn open_by_id(id: &DeviceId) -> Result<Device, Error> {
+    find(id).await?.open().await
+}
+
+/// Find the device identified by `id` again, such as the device the user
+/// picked before the page was reloaded, without opening it or asking the
+/// user to pick it.
+///
+/// Returns `None` if no device matches, or if more than one does, which can
+/// happen when `id` has no serial number and the platform doesn't say where
+/// devices are plugged in. Then the user can be asked to pick the device
+/// again, instead of silently using the wrong one.
+///
+/// **Note:** On WASM, only devices the page has already been given access to
+/// are searched. With the `serde` feature, a [`DeviceId`] can be saved (for
+/// example in `localStorage`) and loaded again after a reload.
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// # let saved_id: cross_usb::usb::DeviceId = unimplemented!();
+/// use cross_usb::device_filter;
+///
+/// let device_info = match cross_usb::rebind(&saved_id).await.expect("Failed to list devices") {
+///     Some(device_info) => device_info,
+///     None => {
+///         let filter = vec![device_filter!{vendor_id: saved_id.vendor_id, product_id: saved_id.product_id}];
+///         cross_usb::request_device(filter).await.expect("No device was picked")
+///     }
+/// };
+/// # })
+/// ```
+pub async fn rebind(id: &DeviceId) -> Result<Option<DeviceInfo>, Error> {
+    match find(id).await {
+        Ok(device_info) => Ok(Some(device_info)),
+        Err(Error::DeviceNotFound | Error::AmbiguousDevice) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Gets a single device as with [`crate::get_device`], other than the ones
+/// identified by `held`, such as devices which are already open. This makes
+/// it possible to use several identical devices at once.
+///
+/// A matching device which can be used without asking is returned first.
+/// Otherwise the user is asked to pick one, and held devices with serial
+/// numbers are left out of the browser's chooser. If the user picks a held
+/// device anyway, this fails with [`Error::DeviceNotFound`].
+///
+/// **Note:** On WASM, devices are only told apart by their serial numbers,
+/// so devices without one can't be held apart from identical devices. Asking
+/// the user must happen in response to a user gesture, as with
+/// [`crate::request_device`].
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::prelude::*;
+/// use cross_usb::device_filter;
+///
+/// let filter = vec![device_filter!{vendor_id: 0x1d50, product_id: 0x6089}];
+///
+/// let first = cross_usb::get_device(filter.clone()).await.expect("No device was picked");
+/// let held = vec![first.id().await];
+///
+/// // When the "Add another device" button is clicked
+/// let second = cross_usb::get_device_excluding(filter, &held).await.expect("No device was picked");
+/// # })
+/// ```
+pub async fn get_device_excluding(device_filters: Vec<DeviceFilter>, held: &[DeviceId]) -> Result<DeviceInfo, Error> {
+    let mut available = Vec::new();
+    for device_info in crate::context::list_devices().await? {
+        if !is_held(held, &device_info).await {
+            available.push(device_info);
+        }
+    }
+
+    if let Some(device_info) = filter::best(&device_filters, available).await {
+        return Ok(device_info);
+    }
+
+    // The chooser can leave out devices by serial number, which doesn't
+    // change which devices match otherwise
+    let mut device_filters = device_filters;
+    device_filters.extend(held.iter().filter_map(|id| {
+        Some(DeviceFilter {
+            vendor_id: Some(id.vendor_id),
+            product_id: Some(id.product_id),
+            serial_number: Some(id.serial_number.clone()?),
+            exclude: true,
+            ..DeviceFilter::default()
+        })
+    }));
+
+    let options = GetDeviceOptions {
+        allow_prompt: true,
+        prefer_paired: false,
+    };
+    let device_info = crate::context::get_device_with_options(device_filters, options).await?;
+    if is_held(held, &device_info).await {
+        return Err(Error::DeviceNotFound);
+    }
+
+    Ok(device_info)
+}
+
+async fn is_held(held: &[DeviceId], device_info: &DeviceInfo) -> bool {
+    let id = device_info.id().await;
+    held.iter().any(|held| held.matches(&id))
+}
+
+/// The only listed device matching `id`.
+async fn find(id: &DeviceId) -> Result<DeviceInfo, Error> {
+    let mut found = None;
+
+    for device_info in crate::context::list_devices().await? {
+        if !id.matches(&device_info.id().await) {
+            continue;
+        }
+
+        if found.is_some() {
+            return Err(Error::AmbiguousDevice);
+        }
+        found = Some(device_info);
+    }
+
+    found.ok_or(Error::DeviceNotFound)
+}
@@ -0,0 +1,902 @@
+//! Adapters which expose the endpoints of an [`Interface`] through common
+//! asynchronous I/O traits, so that existing protocol code can be used with
+//! USB devices on both native and WASM targets.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_sink::Sink;
+use futures_timer::Delay;
+
+use crate::usb::{Error, MaybeSend, UsbInterface};
+use crate::Interface;
+
+/// A boxed transfer which can be stored inside of an adapter and polled
+/// to completion.
+#[cfg(not(target_family = "wasm"))]
+type TransferFuture<T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send>>;
+
+#[cfg(target_family = "wasm")]
+type TransferFuture<T> = Pin<Box<dyn Future<Output = Result<T, Error>>>>;
+
+impl Interface {
+    /// Create a [`BulkReader`] which reads from a bulk IN `endpoint` of this
+    /// interface, requesting `transfer_size` bytes at a time.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// use futures::io::AsyncReadExt;
+    ///
+    /// let interface = device.open_interface(0).await.expect("Failed to open interface");
+    ///
+    /// let mut reader = interface.reader(0x81, 512);
+    /// let mut header = [0; 4];
+    /// reader.read_exact(&mut header).await.expect("Failed to read header");
+    /// # })
+    /// ```
+    pub fn reader(&self, endpoint: u8, transfer_size: usize) -> BulkReader {
+        BulkReader {
+            interface: self.clone(),
+            endpoint,
+            transfer_size,
+            read_ahead: self.in_flight(endpoint),
+            disconnected: false,
+            buffer: Vec::new(),
+            position: 0,
+            in_flight: InFlight::new(),
+        }
+    }
+
+    /// Create a [`BulkWriter`] which writes to a bulk OUT `endpoint` of this
+    /// interface, sending at most `transfer_size` bytes per transfer.
+    pub fn writer(&self, endpoint: u8, transfer_size: usize) -> BulkWriter {
+        BulkWriter {
+            interface: self.clone(),
+            endpoint,
+            transfer_size,
+            pacing: Pacing::default(),
+            pending: None,
+        }
+    }
+
+    /// Create a [`BulkSink`] which writes buffers to a bulk OUT `endpoint`
+    /// of this interface.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// use futures::SinkExt;
+    ///
+    /// let interface = device.open_interface(0).await.expect("Failed to open interface");
+    ///
+    /// let mut sink = interface.sink(0x02);
+    /// sink.send(vec![0x01, 0x02, 0x03]).await.expect("Failed to send buffer");
+    /// # })
+    /// ```
+    pub fn sink(&self, endpoint: u8) -> BulkSink {
+        BulkSink {
+            interface: self.clone(),
+            endpoint,
+            max_pending: BulkSink::DEFAULT_MAX_PENDING,
+            queue: VecDeque::new(),
+            pacing: Pacing::default(),
+            in_flight: None,
+        }
+    }
+
+    /// Create a [`PipelinedWriter`] which keeps up to `depth` bulk OUT
+    /// transfers in flight on `endpoint` at once.
+    ///
+    /// **Note:** WebUSB has no way to queue transfers ahead of time, so on
+    /// WASM the depth is always 1 and transfers are sent one after another.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// # let samples: Vec<Vec<u8>> = vec![];
+    /// let interface = device.open_interface(0).await.expect("Failed to open interface");
+    ///
+    /// let mut writer = interface.bulk_out_pipelined(0x02, 8);
+    /// for buffer in samples {
+    ///     // Waits for space in the pipeline before submitting
+    ///     writer.write(buffer).await.expect("Transfer failed");
+    /// }
+    /// writer.flush().await.expect("Transfer failed");
+    /// # })
+    /// ```
+    pub fn bulk_out_pipelined(&self, endpoint: u8, depth: usize) -> PipelinedWriter {
+        let depth = if cfg!(target_family = "wasm") { 1 } else { depth.max(1) };
+
+        PipelinedWriter {
+            interface: self.clone(),
+            endpoint,
+            depth,
+            in_flight: InFlight::new(),
+        }
+    }
+
+    /// Send all of `data` to a bulk OUT `endpoint` in transfers of at most
+    /// `chunk_size` bytes, calling `progress` with the number of bytes sent so
+    /// far and the total after each one completes.
+    ///
+    /// If a transfer fails, `progress` is called one last time with the number
+    /// of bytes which made it to the device, and the error is an
+    /// [`Error::PartialTransfer`] holding that number if it isn't zero, so the
+    /// upload can be resumed from there.
+    ///
+    /// `progress` has to be [`Send`] on native targets, but not on WASM.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// # let image: Vec<u8> = vec![];
+    /// let interface = device.open_interface(0).await.expect("Failed to open interface");
+    ///
+    /// interface
+    ///     .bulk_out_all_with_progress(0x02, &image, 4096, |sent, total| {
+    ///         println!("{sent} of {total} bytes sent");
+    ///     })
+    ///     .await
+    ///     .expect("Failed to send image");
+    /// # })
+    /// ```
+    pub async fn bulk_out_all_with_progress<F>(
+        &self,
+        endpoint: u8,
+        data: &[u8],
+        chunk_size: usize,
+        mut progress: F,
+    ) -> Result<usize, Error>
+    where
+        F: FnMut(usize, usize) + MaybeSend,
+    {
+        let total = data.len();
+        let mut sent = 0;
+
+        for chunk in data.chunks(chunk_size.max(1)) {
+            match self.bulk_out(endpoint, chunk).await {
+                Ok(written) => sent += written,
+                Err(Error::PartialTransfer { written, source }) => {
+                    progress(sent + written, total);
+                    return Err(Error::partial(sent + written, *source));
+                }
+                Err(err) => {
+                    progress(sent, total);
+                    return Err(Error::partial(sent, err));
+                }
+            }
+
+            progress(sent, total);
+        }
+
+        Ok(sent)
+    }
+
+    /// Read everything from `reader` and send it to a bulk OUT `endpoint`, in
+    /// transfers of `chunk_size` bytes. The last transfer holds whatever is
+    /// left over, and if `zero_length_packet` is set an empty transfer is sent
+    /// after it to mark the end of the data.
+    ///
+    /// Returns the total number of bytes sent. If reading fails the error is
+    /// an [`Error::Read`], otherwise it is the error from the transfer.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// # let image: Vec<u8> = vec![];
+    /// let interface = device.open_interface(0).await.expect("Failed to open interface");
+    ///
+    /// let firmware = futures::io::Cursor::new(image);
+    /// let sent = interface
+    ///     .send_from(0x02, firmware, 4096, true)
+    ///     .await
+    ///     .expect("Failed to send firmware");
+    /// # })
+    /// ```
+    pub async fn send_from<R: AsyncRead + Unpin>(
+        &self,
+        endpoint: u8,
+        mut reader: R,
+        chunk_size: usize,
+        zero_length_packet: bool,
+    ) -> Result<u64, Error> {
+        let mut chunk = vec![0; chunk_size.max(1)];
+        let mut total = 0;
+
+        loop {
+            let length = fill(&mut reader, &mut chunk).await?;
+            if length == 0 {
+                break;
+            }
+
+            total += self.bulk_out(endpoint, &chunk[..length]).await? as u64;
+
+            if length < chunk.len() {
+                break;
+            }
+        }
+
+        if zero_length_packet {
+            self.bulk_out(endpoint, &[]).await?;
+        }
+
+        Ok(total)
+    }
+}
+
+/// Read from `reader` until `buf` is full or the end of the stream.
+async fn fill<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        let read = std::future::poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut buf[filled..])).await;
+
+        match read {
+            Ok(0) => break,
+            Ok(length) => filled += length,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => {
+                return Err(Error::Read {
+                    kind: err.kind(),
+                    message: err.to_string(),
+                })
+            }
+        }
+    }
+
+    Ok(filled)
+}
+
+/// Spacing between the bulk OUT transfers of a writer or sink.
+#[derive(Clone, Copy, Default)]
+struct Pacing {
+    delay: Duration,
+    bytes_per_sec: Option<u64>,
+}
+
+impl Pacing {
+    /// Run a transfer of `length` bytes, only completing once it's time for
+    /// the next transfer to start.
+    async fn run(self, length: usize, transfer: impl Future<Output = Result<usize, Error>>) -> Result<usize, Error> {
+        let budget = self
+            .bytes_per_sec
+            .map(|rate| Delay::new(Duration::from_secs_f64(length as f64 / rate as f64)));
+
+        let written = transfer.await?;
+
+        if let Some(budget) = budget {
+            budget.await;
+        }
+
+        if !self.delay.is_zero() {
+            Delay::new(self.delay).await;
+        }
+
+        Ok(written)
+    }
+}
+
+/// Transfers which have been submitted together, and are completed in the
+/// order they were submitted.
+struct InFlight<T> {
+    slots: VecDeque<Slot<T>>,
+}
+
+enum Slot<T> {
+    Pending(TransferFuture<T>),
+    Done(Result<T, Error>),
+}
+
+impl<T> InFlight<T> {
+    fn new() -> Self {
+        Self {
+            slots: VecDeque::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn push(&mut self, transfer: TransferFuture<T>) {
+        self.slots.push_back(Slot::Pending(transfer));
+    }
+
+    fn clear(&mut self) {
+        self.slots.clear();
+    }
+
+    /// Poll every transfer which has not yet completed. This is what
+    /// actually submits a transfer, so it must be called after pushing.
+    fn poll_all(&mut self, cx: &mut Context<'_>) {
+        for slot in self.slots.iter_mut() {
+            if let Slot::Pending(transfer) = slot {
+                if let Poll::Ready(result) = transfer.as_mut().poll(cx) {
+                    *slot = Slot::Done(result);
+                }
+            }
+        }
+    }
+
+    /// Get the result of the oldest transfer, once it has completed.
+    /// Returns `None` if there are no transfers in flight.
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, Error>>> {
+        self.poll_all(cx);
+
+        match self.slots.front() {
+            None => Poll::Ready(None),
+            Some(Slot::Pending(_)) => Poll::Pending,
+            Some(Slot::Done(_)) => match self.slots.pop_front() {
+                Some(Slot::Done(result)) => Poll::Ready(Some(result)),
+                _ => unreachable!(),
+            },
+        }
+    }
+}
+
+/// Reads from a bulk IN endpoint through [`AsyncRead`].
+///
+/// One bulk transfer is buffered internally, and reads are served from that
+/// buffer until it is exhausted before another transfer is submitted. A short
+/// transfer from the device results in a short read, and zero length transfers
+/// are skipped rather than being reported as the end of the stream.
+///
+/// By default the number of transfers in flight at a time is the one set for
+/// the endpoint with [`UsbInterface::set_in_flight`], which is 1 unless it was
+/// changed. When streaming, use [`BulkReader::set_read_ahead`] to keep more
+/// transfers submitted so data isn't dropped while the host is busy elsewhere.
+///
+/// Errors are reported as [`std::io::Error`]s. A stalled endpoint results in
+/// [`std::io::ErrorKind::BrokenPipe`], and a disconnected device results in
+/// [`std::io::ErrorKind::NotConnected`]. Once the device has disconnected, no
+/// more transfers are submitted and every read fails. After any other error,
+/// reading carries on with the transfers which were already in flight.
+pub struct BulkReader {
+    interface: Interface,
+    endpoint: u8,
+    transfer_size: usize,
+    read_ahead: usize,
+    disconnected: bool,
+    buffer: Vec<u8>,
+    position: usize,
+    in_flight: InFlight<Vec<u8>>,
+}
+
+impl BulkReader {
+    /// The endpoint this reader reads from.
+    pub fn endpoint(&self) -> u8 {
+        self.endpoint
+    }
+
+    /// The number of transfers kept in flight while reading.
+    pub fn read_ahead(&self) -> usize {
+        self.read_ahead
+    }
+
+    /// Set the number of transfers to keep in flight while reading. Data is
+    /// always returned in the order the transfers were submitted.
+    ///
+    /// **Note:** WebUSB has no way to queue transfers ahead of time, so on
+    /// WASM this has no effect and one transfer is in flight at a time.
+    pub fn set_read_ahead(&mut self, depth: usize) {
+        self.read_ahead = if cfg!(target_family = "wasm") { 1 } else { depth.max(1) };
+    }
+
+    fn submit(&self) -> TransferFuture<Vec<u8>> {
+        let interface = self.interface.clone();
+        let endpoint = self.endpoint;
+        let transfer_size = self.transfer_size;
+
+        Box::pin(async move { interface.bulk_in(endpoint, transfer_size).await })
+    }
+
+    fn poll_read_inner(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, Error>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        while self.position >= self.buffer.len() {
+            // Don't keep submitting transfers to a device which is gone
+            if self.disconnected {
+                return Poll::Ready(Err(Error::Disconnected));
+            }
+
+            while self.in_flight.len() < self.read_ahead {
+                let transfer = self.submit();
+                self.in_flight.push(transfer);
+            }
+
+            let Some(result) = ready!(self.in_flight.poll_next(cx)) else {
+                unreachable!("transfers were just submitted");
+            };
+
+            self.buffer = match result {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    // Transfers submitted after the one which failed may
+                    // already hold data, so they're only dropped once the
+                    // device is gone
+                    if err == Error::Disconnected {
+                        self.in_flight.clear();
+                        self.disconnected = true;
+                    }
+                    return Poll::Ready(Err(err));
+                }
+            };
+            self.position = 0;
+        }
+
+        let length = buf.len().min(self.buffer.len() - self.position);
+        buf[..length].copy_from_slice(&self.buffer[self.position..self.position + length]);
+        self.position += length;
+
+        Poll::Ready(Ok(length))
+    }
+}
+
+impl AsyncRead for BulkReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.get_mut()
+            .poll_read_inner(cx, buf)
+            .map_err(std::io::Error::from)
+    }
+}
+
+impl std::fmt::Debug for BulkReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BulkReader {:?}", self.endpoint)
+    }
+}
+
+/// Writes to a bulk OUT endpoint through [`AsyncWrite`].
+///
+/// Each write submits a single bulk transfer of at most the configured
+/// transfer size, and one transfer is kept in flight at a time. If a transfer
+/// fails, the error is returned from the next call to write, flush or close.
+pub struct BulkWriter {
+    interface: Interface,
+    endpoint: u8,
+    transfer_size: usize,
+    pacing: Pacing,
+    pending: Option<TransferFuture<usize>>,
+}
+
+impl BulkWriter {
+    /// The endpoint this writer writes to.
+    pub fn endpoint(&self) -> u8 {
+        self.endpoint
+    }
+
+    /// Wait at least `delay` after each transfer completes before starting
+    /// the next one, for devices which can't keep up with back-to-back
+    /// transfers. Defaults to no delay.
+    pub fn set_inter_transfer_delay(&mut self, delay: Duration) {
+        self.pacing.delay = delay;
+    }
+
+    /// Limit the rate data is sent at to roughly `bytes_per_sec`, by spacing
+    /// out transfers. `None` (the default) sends data as fast as possible.
+    pub fn set_max_throughput(&mut self, bytes_per_sec: Option<u64>) {
+        self.pacing.bytes_per_sec = bytes_per_sec.filter(|rate| *rate > 0);
+    }
+
+    fn poll_write_inner(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, Error>> {
+        ready!(self.poll_flush_inner(cx))?;
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let length = buf.len().min(self.transfer_size);
+        let data = buf[..length].to_vec();
+
+        let interface = self.interface.clone();
+        let endpoint = self.endpoint;
+        let pacing = self.pacing;
+        self.pending = Some(Box::pin(async move {
+            pacing.run(data.len(), interface.bulk_out_owned(endpoint, data)).await
+        }));
+
+        Poll::Ready(Ok(length))
+    }
+
+    fn poll_flush_inner(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        if let Some(pending) = self.pending.as_mut() {
+            let result = ready!(pending.as_mut().poll(cx));
+            self.pending = None;
+
+            result?;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for BulkWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.get_mut()
+            .poll_write_inner(cx, buf)
+            .map_err(std::io::Error::from)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut()
+            .poll_flush_inner(cx)
+            .map_err(std::io::Error::from)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl std::fmt::Debug for BulkWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BulkWriter {:?}", self.endpoint)
+    }
+}
+
+/// Writes buffers to a bulk OUT endpoint through [`Sink`].
+///
+/// Each buffer is sent as a single bulk transfer, in the order they were
+/// given to the sink. [`Sink::poll_ready`] applies backpressure once the
+/// number of buffers waiting to be written reaches the limit set with
+/// [`BulkSink::set_max_pending`], and [`Sink::poll_flush`] resolves once every
+/// buffer has been written.
+///
+/// If a transfer fails, the error is returned from the next call to
+/// `poll_ready`, `poll_flush` or `poll_close`, and any buffers still waiting
+/// to be written are discarded.
+pub struct BulkSink {
+    interface: Interface,
+    endpoint: u8,
+    max_pending: usize,
+    queue: VecDeque<Vec<u8>>,
+    pacing: Pacing,
+    in_flight: Option<TransferFuture<usize>>,
+}
+
+impl BulkSink {
+    /// The default number of buffers which may be waiting to be written
+    /// before the sink applies backpressure.
+    pub const DEFAULT_MAX_PENDING: usize = 4;
+
+    /// The endpoint this sink writes to.
+    pub fn endpoint(&self) -> u8 {
+        self.endpoint
+    }
+
+    /// Wait at least `delay` after each transfer completes before starting
+    /// the next one, for devices which can't keep up with back-to-back
+    /// transfers. Defaults to no delay.
+    pub fn set_inter_transfer_delay(&mut self, delay: Duration) {
+        self.pacing.delay = delay;
+    }
+
+    /// Limit the rate data is sent at to roughly `bytes_per_sec`, by spacing
+    /// out transfers. `None` (the default) sends data as fast as possible.
+    pub fn set_max_throughput(&mut self, bytes_per_sec: Option<u64>) {
+        self.pacing.bytes_per_sec = bytes_per_sec.filter(|rate| *rate > 0);
+    }
+
+    /// Set the number of buffers which may be waiting to be written before
+    /// the sink applies backpressure. This is always at least 1.
+    pub fn set_max_pending(&mut self, max_pending: usize) {
+        self.max_pending = max_pending.max(1);
+    }
+
+    /// The number of buffers which are waiting to be written, including the
+    /// one currently being transferred.
+    pub fn pending(&self) -> usize {
+        self.queue.len() + self.in_flight.is_some() as usize
+    }
+
+    /// Write queued buffers until the queue is empty or a transfer is still
+    /// in progress.
+    fn poll_drive(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        loop {
+            if let Some(in_flight) = self.in_flight.as_mut() {
+                let result = ready!(in_flight.as_mut().poll(cx));
+                self.in_flight = None;
+
+                if let Err(err) = result {
+                    self.queue.clear();
+                    return Poll::Ready(Err(err));
+                }
+            }
+
+            let Some(data) = self.queue.pop_front() else {
+                return Poll::Ready(Ok(()));
+            };
+
+            let interface = self.interface.clone();
+            let endpoint = self.endpoint;
+            let pacing = self.pacing;
+            self.in_flight = Some(Box::pin(async move {
+                pacing.run(data.len(), interface.bulk_out_owned(endpoint, data)).await
+            }));
+        }
+    }
+}
+
+impl Sink<Vec<u8>> for BulkSink {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+
+        match this.poll_drive(cx) {
+            Poll::Ready(result) => Poll::Ready(result),
+            Poll::Pending if this.pending() < this.max_pending => Poll::Ready(Ok(())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Vec<u8>) -> Result<(), Self::Error> {
+        self.get_mut().queue.push_back(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_drive(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl std::fmt::Debug for BulkSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BulkSink {:?}", self.endpoint)
+    }
+}
+
+/// Writes to a bulk OUT endpoint with several transfers in flight at once.
+///
+/// Keeping more than one transfer submitted means the device never has to
+/// wait for the host to submit the next one, which is needed to saturate
+/// fast links. Use [`PipelinedWriter::ready`] to wait for space in the
+/// pipeline, or [`PipelinedWriter::write`] which does so before submitting.
+///
+/// If a transfer fails, its error is returned from the next call to `ready`,
+/// `write` or `flush`, and every other transfer in flight is cancelled.
+pub struct PipelinedWriter {
+    interface: Interface,
+    endpoint: u8,
+    depth: usize,
+    in_flight: InFlight<usize>,
+}
+
+impl PipelinedWriter {
+    /// The endpoint this writer writes to.
+    pub fn endpoint(&self) -> u8 {
+        self.endpoint
+    }
+
+    /// The maximum number of transfers kept in flight.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The number of transfers which have been submitted but whose
+    /// completion has not been observed yet.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Wait until another buffer can be submitted without exceeding the
+    /// depth of the pipeline.
+    pub async fn ready(&mut self) -> Result<(), Error> {
+        std::future::poll_fn(|cx| self.poll_ready(cx)).await
+    }
+
+    /// Submit `data` as a single bulk transfer, first waiting for space in
+    /// the pipeline. This returns once the transfer has been submitted, not
+    /// once it has completed.
+    pub async fn write(&mut self, data: Vec<u8>) -> Result<(), Error> {
+        self.ready().await?;
+
+        let interface = self.interface.clone();
+        let endpoint = self.endpoint;
+        self.in_flight
+            .push(Box::pin(async move { interface.bulk_out_owned(endpoint, data).await }));
+
+        // Poll once so the new transfer is submitted right away
+        std::future::poll_fn(|cx| {
+            self.in_flight.poll_all(cx);
+            Poll::Ready(())
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Wait for every transfer in flight to complete.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        std::future::poll_fn(|cx| self.poll_until(cx, 0)).await
+    }
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.poll_until(cx, self.depth - 1)
+    }
+
+    /// Wait for completions until at most `remaining` transfers are in flight.
+    fn poll_until(&mut self, cx: &mut Context<'_>, remaining: usize) -> Poll<Result<(), Error>> {
+        while self.in_flight.len() > remaining {
+            match ready!(self.in_flight.poll_next(cx)) {
+                Some(Ok(_)) | None => {}
+                Some(Err(err)) => {
+                    self.in_flight.clear();
+                    return Poll::Ready(Err(err));
+                }
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl std::fmt::Debug for PipelinedWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PipelinedWriter {:?}", self.endpoint)
+    }
+}
+
+/// Requires the `tokio` feature, and is only available on native targets.
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// # use cross_usb::prelude::*;
+/// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+/// let interface = device.open_interface(0).await.expect("Failed to open interface");
+///
+/// // Echo everything received on one endpoint back out of another
+/// let mut reader = interface.reader(0x81, 512);
+/// let mut writer = interface.writer(0x02, 512);
+/// tokio::io::copy(&mut reader, &mut writer).await.expect("Failed to copy");
+/// # })
+/// ```
+#[cfg(all(feature = "tokio", not(target_family = "wasm")))]
+impl tokio::io::AsyncRead for BulkReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let read = ready!(self.get_mut().poll_read_inner(cx, buf.initialize_unfilled()))?;
+        buf.advance(read);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Requires the `tokio` feature, and is only available on native targets.
+#[cfg(all(feature = "tokio", not(target_family = "wasm")))]
+impl tokio::io::AsyncWrite for BulkWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.get_mut()
+            .poll_write_inner(cx, buf)
+            .map_err(std::io::Error::from)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut()
+            .poll_flush_inner(cx)
+            .map_err(std::io::Error::from)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        tokio::io::AsyncWrite::poll_flush(self, cx)
+    }
+}
+
+/// Requires the `embedded-io-async` feature.
+#[cfg(feature = "embedded-io-async")]
+impl embedded_io_async::ErrorType for BulkReader {
+    type Error = Error;
+}
+
+/// Requires the `embedded-io-async` feature.
+#[cfg(feature = "embedded-io-async")]
+impl embedded_io_async::Read for BulkReader {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        std::future::poll_fn(|cx| self.poll_read_inner(cx, buf)).await
+    }
+}
+
+/// Requires the `embedded-io-async` feature.
+#[cfg(feature = "embedded-io-async")]
+impl embedded_io_async::ErrorType for BulkWriter {
+    type Error = Error;
+}
+
+/// Requires the `embedded-io-async` feature.
+#[cfg(feature = "embedded-io-async")]
+impl embedded_io_async::Write for BulkWriter {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        std::future::poll_fn(|cx| self.poll_write_inner(cx, buf)).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        std::future::poll_fn(|cx| self.poll_flush_inner(cx)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::oneshot;
+    use futures::task::noop_waker;
+
+    fn transfer(receiver: oneshot::Receiver<Result<u8, Error>>) -> TransferFuture<u8> {
+        Box::pin(async move { receiver.await.unwrap() })
+    }
+
+    #[test]
+    fn in_flight_completes_in_submission_order() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let (first, first_receiver) = oneshot::channel();
+        let (second, second_receiver) = oneshot::channel();
+        let mut in_flight = InFlight::new();
+        in_flight.push(transfer(first_receiver));
+        in_flight.push(transfer(second_receiver));
+
+        // The second transfer finishing first doesn't let it jump the queue
+        second.send(Ok(2)).unwrap();
+        assert!(in_flight.poll_next(&mut cx).is_pending());
+        assert_eq!(in_flight.len(), 2);
+
+        first.send(Ok(1)).unwrap();
+        assert_eq!(in_flight.poll_next(&mut cx), Poll::Ready(Some(Ok(1))));
+        assert_eq!(in_flight.poll_next(&mut cx), Poll::Ready(Some(Ok(2))));
+        assert_eq!(in_flight.poll_next(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn in_flight_keeps_results_after_an_error() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let (first, first_receiver) = oneshot::channel();
+        let (second, second_receiver) = oneshot::channel();
+        let mut in_flight = InFlight::new();
+        in_flight.push(transfer(first_receiver));
+        in_flight.push(transfer(second_receiver));
+
+        second.send(Ok(2)).unwrap();
+        first.send(Err(Error::Stall)).unwrap();
+        assert_eq!(in_flight.poll_next(&mut cx), Poll::Ready(Some(Err(Error::Stall))));
+        assert_eq!(in_flight.poll_next(&mut cx), Poll::Ready(Some(Ok(2))));
+    }
+}
@@ -10,15 +10,22 @@
 //!
 //! ## CURRENT LIMITATIONS:
 //! * Isochronous and interrupt transfers are currently not supported. This
-//! will probably change in a future release.
-//!
-//! * Hotplug support is not implemented. Waiting on
-//! [hotplug support in nusb](https://github.com/kevinmehall/nusb/pull/20).
+//!   will probably change in a future release.
 //!
 //! * When compiling this crate on a WASM target, you **must** use either
-//! `RUSTFLAGS=--cfg=web_sys_unstable_apis` or by passing the argument in a
-//! `.cargo/config.toml` file. Read more here:
-//! <https://rustwasm.github.io/wasm-bindgen/web-sys/unstable-apis.html>
+//!   `RUSTFLAGS=--cfg=web_sys_unstable_apis` or by passing the argument in a
+//!   `.cargo/config.toml` file. Read more here:
+//!   <https://rustwasm.github.io/wasm-bindgen/web-sys/unstable-apis.html>
+//!
+//! ## Optional features:
+//! * `tokio`: Implements the [`tokio::io`](https://docs.rs/tokio/latest/tokio/io/)
+//!   traits for the endpoint adapters in [`io`]. This has no effect on WASM targets.
+//! * `embedded-io-async`: Implements the
+//!   [`embedded_io_async`](https://docs.rs/embedded-io-async/latest/embedded_io_async/)
+//!   `Read` and `Write` traits for the endpoint adapters in [`io`], with
+//!   [`usb::Error`] as the error type.
+//! * `serde`: Implements `Serialize` and `Deserialize` for the descriptor
+//!   types in [`usb`], such as [`usb::DeviceDescriptor`].
 //!
 //! ## Example:
 //! ```no_run
@@ -55,6 +62,19 @@
 //! # })
 //! ```
 pub mod usb;
+pub mod io;
+pub mod endpoint;
+mod cancel;
+mod descriptor;
+mod filter;
+mod id;
+mod request;
+mod resilient;
+mod retry;
+mod status;
+mod timer;
+mod transfer;
+mod watch;
 
 /// This prelude imports all the necessary traits needed to actually use USB
 /// devices and interfaces.
@@ -98,13 +118,137 @@ pub use crate::context::Interface;
 /// Information about a USB device for use in [`get_device`] or
 /// [`get_device_list`].
 ///
+/// A device matches a filter if every field which is set matches, and a
+/// device is found if it matches any filter in the list. A filter with no
+/// fields set matches nothing. The class, subclass and protocol match the
+/// device itself or any one of its interfaces, see [`DeviceFilter::matches_info`].
+///
+/// A filter with `exclude` set to `true` removes the devices it matches
+/// instead, so a device is found if it matches any filter which doesn't set
+/// `exclude`, and none which do. On WASM, these are passed to the browser as
+/// `exclusionFilters`.
+///
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::device_filter;
+///
+/// // Any vendor specific device, except ones from vendor 0x1234
+/// let filter = vec![
+///     device_filter!{class: 0xFF},
+///     device_filter!{vendor_id: 0x1234, exclude: true},
+/// ];
+///
+/// let device = cross_usb::get_device(filter).await.expect("Could not find device matching filters");
+/// # })
+/// ```
+///
+/// **Note:** The `bus_number` and `port_chain` fields are only available on
+/// native, as browsers don't say where a device is plugged in. Devices only
+/// match a `port_chain` on platforms which report it, see
+/// [`usb::UsbDeviceInfo::port_chain`].
+///
+/// **Note:** On WASM, the browser can't match the `manufacturer_string_contains`,
+/// `product_string_contains`, `device_version_min` and `device_version_max`
+/// fields when it asks the user to pick a device, so they only apply to
+/// devices the page already has access to.
+///
+/// `device_version_min` and `device_version_max` are inclusive bounds on the
+/// device's release number (`bcdDevice`).
+///
 /// It's easiest to construct this using the [`device_filter`]
-/// macro.
+/// macro, or [`DeviceFilter::builder`] when the fields are only known at
+/// runtime.
 #[doc(inline)]
 pub use crate::context::DeviceFilter;
 
-/// Gets a single (the first found) device as a [`DeviceInfo`] from a list of VendorID
-/// and ProductIDs
+#[doc(inline)]
+pub use crate::filter::DeviceFilterBuilder;
+
+/// Whether USB devices can be used here, to show a helpful message when they
+/// can't instead of failing later.
+///
+/// This is always `true` on native. On WASM, it's `false` in browsers which
+/// don't support WebUSB, such as Firefox and Safari, and in pages which
+/// aren't in a secure context (served over HTTPS or from `localhost`).
+/// Finding devices there fails with [`usb::Error::NotSupported`].
+///
+/// ## Example
+/// ```
+/// if !cross_usb::is_supported() {
+///     println!("Your browser doesn't support WebUSB, try Chrome or Edge");
+/// }
+/// ```
+#[doc(inline)]
+pub use crate::context::is_supported;
+
+/// Whether the page may use USB devices, so it can decide between showing a
+/// "Connect" button and reconnecting without asking.
+///
+/// This is always [`usb::PermissionState::Granted`] on native. On WASM it
+/// comes from the browser's Permissions API, and is
+/// [`usb::PermissionState::Unsupported`] in browsers which can't be asked
+/// about the `usb` permission. Fails with [`usb::Error::NotSupported`] if
+/// the browser doesn't support WebUSB at all.
+///
+/// **Note:** On WASM, access is given to each device separately, so use
+/// [`get_paired_devices`] to find out which devices can be used without
+/// asking.
+///
+/// ## Example
+/// ```
+/// # tokio_test::block_on(async {
+/// use cross_usb::usb::PermissionState;
+///
+/// match cross_usb::permission_state().await {
+///     Ok(PermissionState::Denied) => println!("USB devices are blocked on this page"),
+///     Ok(_) => { /* Try to reconnect, or show a "Connect" button */ }
+///     Err(_) => println!("Your browser doesn't support WebUSB"),
+/// }
+/// # })
+/// ```
+#[doc(inline)]
+pub use crate::context::permission_state;
+
+/// What works on the current target, so that code which runs on several
+/// targets can fall back when something isn't available.
+///
+/// ## Example
+/// ```
+/// let capabilities = cross_usb::capabilities();
+/// if !capabilities.enumerate_without_prompt {
+///     println!("Click \"Connect\" to pick your device");
+/// }
+/// ```
+#[doc(inline)]
+pub use crate::context::capabilities;
+
+/// Gets a single device as a [`DeviceInfo`] which matches any of
+/// `device_filters`, choosing the best match when several devices match.
+///
+/// If devices match different filters, the device matching the filter which
+/// sets the most fields (see [`DeviceFilter::specificity`]) is returned, then
+/// the one matching the filter which comes first in the list. Devices which
+/// match equally well are returned in the order of [`list_devices`] on
+/// native. To choose between several identical devices, use
+/// [`get_device_list`] instead, and to find out which filter the device
+/// matched, use [`get_device_matched`].
+///
+/// A list without filters which include devices, such as an empty list,
+/// matches no devices and fails with [`usb::Error::DeviceNotFound`]. On WASM
+/// the user isn't asked to pick a device then, as WebUSB needs at least one
+/// filter to show the chooser.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use cross_usb::usb::Error;
+/// use cross_usb::device_filter;
+///
+/// assert_eq!(cross_usb::get_device(vec![]).await.unwrap_err(), Error::DeviceNotFound);
+///
+/// let exclude_only = vec![device_filter!{vendor_id: 0x054c, exclude: true}];
+/// assert_eq!(cross_usb::get_device(exclude_only).await.unwrap_err(), Error::DeviceNotFound);
+/// # })
+/// ```
 ///
 /// ## Example
 /// ```no_run
@@ -122,8 +266,88 @@ pub use crate::context::DeviceFilter;
 #[doc(inline)]
 pub use crate::context::get_device;
 
+/// Gets a single device as a [`DeviceInfo`], as with [`get_device`], with
+/// control over when the user is asked to pick one.
+///
+/// With [`usb::GetDeviceOptions::allow_prompt`] set to `false`, the
+/// browser's chooser is never shown, so this can run when the page loads.
+/// It fails with [`usb::Error::DeviceNotFound`] if the page doesn't have
+/// access to a matching device. With [`usb::GetDeviceOptions::prefer_paired`]
+/// set to `false`, the user is always asked to pick a device, which must
+/// happen in response to a user gesture.
+///
+/// **Note:** On native there is no chooser, so the options are ignored and
+/// this is the same as [`get_device`].
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::device_filter;
+/// use cross_usb::usb::GetDeviceOptions;
+///
+/// let filter = vec![device_filter!{vendor_id: 0x054c, product_id: 0x00c9}];
+///
+/// // When the page loads, reconnect without showing the chooser
+/// let options = GetDeviceOptions { allow_prompt: false, ..Default::default() };
+/// match cross_usb::get_device_with_options(filter, options).await {
+///     Ok(device_info) => { /* Open the device */ }
+///     Err(_) => { /* Show a "Connect" button instead */ }
+/// }
+/// # })
+/// ```
+#[doc(inline)]
+pub use crate::context::get_device_with_options;
+
+/// Gets a single (the first found) device as a [`DeviceInfo`] which matches
+/// any of `device_filters` and is accepted by `predicate`, for conditions the
+/// fields of a [`DeviceFilter`] can't express.
+///
+/// `predicate` is given the same information the filters are matched
+/// against. On native, a list without filters which include devices leaves
+/// `predicate` to check every device which isn't excluded.
+///
+/// **Note:** On WASM, devices the page hasn't been given access to can only
+/// be found by the browser, which asks the user to pick one of the devices
+/// matching `device_filters`. `predicate` can only refine that choice, and
+/// the device the user picks is rejected with [`usb::Error::DeviceNotFound`]
+/// if it doesn't accept it.
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::{get_device_with, DeviceFilter, device_filter};
+///
+/// let filter = vec![device_filter!{vendor_id: 0x054c}];
+///
+/// let device = get_device_with(filter, |info| {
+///     (0x00c0..0x00d0).contains(&info.product_id)
+///         && info.serial_number_string.as_deref().is_some_and(|serial| serial.starts_with("PROD-"))
+/// })
+/// .await
+/// .expect("Could not find a production device");
+/// # })
+/// ```
+#[doc(inline)]
+pub use crate::context::get_device_with;
+
 /// Gets a list of [`DeviceInfo`]s from a list of VendorID and ProductIDs
 ///
+/// An empty list of filters matches no devices, and fails with
+/// [`usb::Error::DeviceNotFound`]. Use [`list_devices`] to list every device.
+///
+/// Devices are listed once each, in the same order as [`list_devices`], so
+/// identical devices are always listed in the same order while nothing is
+/// plugged in or removed. They can be told apart by their serial numbers
+/// ([`usb::UsbDeviceInfo::serial_number_string`]) if they have them, or by
+/// where they are plugged in ([`usb::UsbDeviceInfo::bus_number`] and
+/// [`usb::UsbDeviceInfo::port_chain`]). [`DeviceInfo::id`] combines these
+/// into a [`usb::DeviceId`] which finds the same device again later.
+///
+/// **Note:** On WASM, only the devices the page already has access to are
+/// listed, and the user is never asked to pick one. Use [`get_device`] to ask
+/// for access to a device. The devices aren't opened until
+/// [`usb::UsbDeviceInfo::open`] is called.
+///
 /// ## Example
 /// ```no_run
 /// # tokio_test::block_on(async {
@@ -135,14 +359,160 @@ pub use crate::context::get_device;
 /// ];
 ///
 /// let device_list = get_device_list(filter).await.expect("Could not find device matching filters");
+/// println!("Found {} devices", device_list.len());
 ///
 /// /* Do something with the list of devices... */
 /// # })
 /// ```
-#[cfg(not(target_family = "wasm"))]
+///
+/// Picking one of several identical devices:
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::prelude::*;
+/// use cross_usb::{get_device_list, device_filter};
+///
+/// let filter = vec![device_filter!{vendor_id: 0x054c, product_id: 0x00c9}];
+/// let device_list = get_device_list(filter).await.expect("Could not find device matching filters");
+///
+/// for (index, device_info) in device_list.iter().enumerate() {
+///     println!(
+///         "[{index}] serial {:?}, port {:?}",
+///         device_info.serial_number_string().await,
+///         device_info.port_chain().await,
+///     );
+/// }
+///
+/// // The second one, as listed above
+/// let device = device_list.into_iter().nth(1).expect("No second device").open().await;
+/// # })
+/// ```
 #[doc(inline)]
 pub use crate::context::get_device_list;
 
+/// Gets a list of [`DeviceInfo`]s which match any of `device_filters` and are
+/// accepted by `predicate`, as with [`get_device_with`].
+///
+/// A list without filters which include devices leaves `predicate` to check
+/// every device which isn't excluded.
+///
+/// **Note:** On WASM, only the devices the page already has access to are
+/// checked, and the user is never asked to pick one. `predicate` can only
+/// refine that list, and can't give the page access to any other device.
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::get_device_list_with;
+///
+/// // Every device with a serial number
+/// let device_list = get_device_list_with(vec![], |info| info.serial_number_string.is_some())
+///     .await
+///     .expect("Could not find any devices");
+///
+/// for (index, device_info) in device_list.iter().enumerate() {
+///     println!("[{index}] {:?}", device_info);
+/// }
+/// # })
+/// ```
+#[doc(inline)]
+pub use crate::context::get_device_list_with;
+
+/// Lists every USB device connected to the system, without opening them.
+///
+/// Devices are sorted by their bus number and then their address, so the
+/// order is the same every time while nothing is plugged in or removed.
+///
+/// **Note:** This is not available on WASM, where a page can only see the
+/// devices the user has picked with [`get_device`].
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::prelude::*;
+///
+/// for device_info in cross_usb::list_devices().await.expect("Failed to list devices") {
+///     println!(
+///         "{:04x}:{:04x} {:?}",
+///         device_info.vendor_id().await,
+///         device_info.product_id().await,
+///         device_info.product_string().await,
+///     );
+/// }
+/// # })
+/// ```
+#[cfg(not(target_family = "wasm"))]
+#[doc(inline)]
+pub use crate::context::list_devices;
+
+#[doc(inline)]
+pub use crate::filter::get_paired_devices;
+
+/// Use `usb` instead of `navigator.usb` to find devices, or go back to
+/// `navigator.usb` if it's `None`.
+///
+/// This is for pages where `navigator.usb` isn't the right object, such as a
+/// sandboxed iframe which is given the `USB` object of its parent, and for
+/// tests which stand in a mock object with the same methods and events. The
+/// object is used as it is, without checking for a secure context.
+///
+/// Devices found before the context changes keep working, and the context
+/// applies to the thread it is set on.
+///
+/// **Note:** This is only available on WASM. Native devices come from the
+/// operating system through nusb, which can't be replaced.
+///
+/// ## Example
+/// ```no_run
+/// use wasm_bindgen::JsValue;
+///
+/// // The parent page passed its `navigator.usb` to this frame
+/// let parent_usb: JsValue = js_sys::Reflect::get(&js_sys::global(), &"parentUsb".into()).unwrap();
+/// cross_usb::set_usb_context(Some(parent_usb));
+/// ```
+#[cfg(target_family = "wasm")]
+#[doc(inline)]
+pub use crate::context::set_usb_context;
+
+#[doc(inline)]
+pub use crate::filter::{get_device_list_matched, get_device_matched, MatchedDevice};
+
+/// Asks the user to pick a device matching any of `device_filters`, even if
+/// the page already has access to a matching device.
+///
+/// Fails with [`usb::Error::DeviceNotFound`] if the user closes the chooser
+/// without picking a device, or straight away if none of `device_filters`
+/// include devices.
+///
+/// **Note:** On WASM, this must be called in response to a user gesture,
+/// such as in the handler of a click, or it fails with
+/// [`usb::Error::PermissionDenied`]. On native there is no chooser, so this
+/// is the same as [`get_device`].
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::device_filter;
+///
+/// // When the "Connect another device" button is clicked
+/// let filter = vec![device_filter!{vendor_id: 0x054c}];
+/// let device_info = cross_usb::request_device(filter).await.expect("No device was picked");
+/// # })
+/// ```
+#[doc(inline)]
+pub use crate::context::request_device;
+
+#[doc(inline)]
+pub use crate::request::{prepare_request, DeviceRequest};
+
+#[doc(inline)]
+pub use crate::id::{get_device_excluding, open_by_id, rebind};
+
+#[doc(inline)]
+pub use crate::watch::{wait_for_device, watch_devices, DeviceEvent, DeviceWatch};
+
+#[doc(inline)]
+pub use crate::resilient::{ResilientDevice, ResilientDeviceBuilder};
+
 /// Macro to create a device filter more easily.
 ///
 /// The only valid keys are fields of the [`DeviceFilter`] struct.
@@ -152,24 +522,92 @@ pub use crate::context::get_device_list;
 /// ## Usage
 /// ```
 /// use cross_usb::device_filter;
+/// use cross_usb::usb::Version;
 ///
 /// // Example with all fields filled
 /// device_filter!{
-///     vendor_id: 0x054c,  // u16
-///     product_id: 0x0186, // u16
-///     class: 0xFF,        // u8
-///     subclass: 0x02,     // u8
-///     protocol: 0x15,     // u8
+///     vendor_id: 0x054c,                      // u16
+///     product_id: 0x0186,                     // u16
+///     class: 0xFF,                            // u8
+///     subclass: 0x02,                         // u8
+///     protocol: 0x15,                         // u8
+///     serial_number: "4A3F9C01",              // &str or String
+///     manufacturer_string_contains: "Sony",   // &str or String
+///     product_string_contains: "Serial",      // &str or String
+///     device_version_min: Version::new(1, 1, 0), // Version
+///     device_version_max: Version::new(2, 0, 0), // Version
+///     bus_number: 3,                          // u8, native only
+///     port_chain: vec![1, 4],                 // Vec<u8>, native only
+///     exclude: false,                         // bool
 /// };
 /// ```
+///
+/// Any other key is a compile error naming it:
+/// ```compile_fail
+/// use cross_usb::device_filter;
+///
+/// // error: `vendor` is not a field of `DeviceFilter`
+/// device_filter!{vendor: 0x054c};
+/// ```
 #[macro_export]
 macro_rules! device_filter {
-    ($($field:ident: $val:expr),+ $(,)?) => {
-        cross_usb::DeviceFilter {
-            $($field: Some($val),)*
-            ..cross_usb::DeviceFilter::default()
+    (@set $filter:ident vendor_id: $val:expr) => {
+        $filter.vendor_id = Some($val);
+    };
+    (@set $filter:ident product_id: $val:expr) => {
+        $filter.product_id = Some($val);
+    };
+    (@set $filter:ident class: $val:expr) => {
+        $filter.class = Some($val);
+    };
+    (@set $filter:ident subclass: $val:expr) => {
+        $filter.subclass = Some($val);
+    };
+    (@set $filter:ident protocol: $val:expr) => {
+        $filter.protocol = Some($val);
+    };
+    (@set $filter:ident serial_number: $val:expr) => {
+        $filter.serial_number = Some(String::from($val));
+    };
+    (@set $filter:ident manufacturer_string_contains: $val:expr) => {
+        $filter.manufacturer_string_contains = Some(String::from($val));
+    };
+    (@set $filter:ident product_string_contains: $val:expr) => {
+        $filter.product_string_contains = Some(String::from($val));
+    };
+    (@set $filter:ident device_version_min: $val:expr) => {
+        $filter.device_version_min = Some($val);
+    };
+    (@set $filter:ident device_version_max: $val:expr) => {
+        $filter.device_version_max = Some($val);
+    };
+    (@set $filter:ident bus_number: $val:expr) => {
+        #[cfg(not(target_family = "wasm"))]
+        {
+            $filter.bus_number = Some($val);
+        }
+        #[cfg(target_family = "wasm")]
+        compile_error!("`bus_number` can't be filtered on in WASM");
+    };
+    (@set $filter:ident port_chain: $val:expr) => {
+        #[cfg(not(target_family = "wasm"))]
+        {
+            $filter.port_chain = Some($val);
         }
-    }
+        #[cfg(target_family = "wasm")]
+        compile_error!("`port_chain` can't be filtered on in WASM");
+    };
+    (@set $filter:ident exclude: $val:expr) => {
+        $filter.exclude = $val;
+    };
+    (@set $filter:ident $field:ident: $val:expr) => {
+        compile_error!(concat!("`", stringify!($field), "` is not a field of `DeviceFilter`"));
+    };
+    ($($field:ident: $val:expr),+ $(,)?) => {{
+        let mut filter = $crate::DeviceFilter::default();
+        $($crate::device_filter!(@set filter $field: $val);)*
+        filter
+    }};
 }
 
 #[cfg(all(target_family = "wasm", not(web_sys_unstable_apis)))]
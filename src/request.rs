@@ -0,0 +1,73 @@
+//! Asking the user for a device in a user gesture, and waiting for it
+//! somewhere else.
+
+use crate::context::PendingRequest;
+use crate::usb::Error;
+use crate::{DeviceFilter, DeviceInfo};
+
+/// A request for a device, which asks the user to pick one when
+/// [`DeviceRequest::trigger`] is called, and gives the device they picked to
+/// [`DeviceRequest::device`]. From [`prepare_request`].
+///
+/// Browsers only show the device chooser in response to a user gesture, such
+/// as in the handler of a click, which doesn't fit async code that waits for
+/// the device somewhere else. Clones of a request share it, so one can be
+/// moved into the click handler while another is waited on.
+///
+/// **Note:** On native there is no chooser, so [`DeviceRequest::trigger`]
+/// does nothing and [`DeviceRequest::device`] is the same as
+/// [`crate::get_device`].
+#[derive(Debug, Clone)]
+pub struct DeviceRequest {
+    request: PendingRequest,
+}
+
+impl DeviceRequest {
+    /// Show the browser's device chooser. This must be called synchronously
+    /// from a user gesture, such as in the handler of a click.
+    ///
+    /// Only the first call on any clone of the request shows the chooser.
+    pub fn trigger(&self) {
+        self.request.trigger()
+    }
+
+    /// Wait for the user to pick a device, after [`DeviceRequest::trigger`]
+    /// is called.
+    ///
+    /// Fails with [`Error::DeviceNotFound`] if the user closes the chooser
+    /// without picking a device, or [`Error::PermissionDenied`] if the
+    /// request wasn't triggered from a user gesture. Only one clone of the
+    /// request can wait for the device, and waiting again fails with
+    /// [`Error::DeviceNotFound`].
+    pub async fn device(&self) -> Result<DeviceInfo, Error> {
+        self.request.device().await
+    }
+}
+
+/// Prepare a request for a device matching `device_filters`, which asks the
+/// user to pick one when it is triggered from a user gesture.
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::device_filter;
+///
+/// let filter = vec![device_filter!{vendor_id: 0x054c, product_id: 0x00c9}];
+/// let request = cross_usb::prepare_request(filter);
+///
+/// // Give a clone of the request to the "Connect" button's click handler
+/// let on_click = {
+///     let request = request.clone();
+///     move || request.trigger()
+/// };
+/// # on_click();
+///
+/// // Wait for the device wherever is convenient
+/// let device_info = request.device().await.expect("No device was picked");
+/// # })
+/// ```
+pub fn prepare_request(device_filters: Vec<DeviceFilter>) -> DeviceRequest {
+    DeviceRequest {
+        request: PendingRequest::new(device_filters),
+    }
+}
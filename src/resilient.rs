@@ -0,0 +1,306 @@
+//! Devices which are found and opened again automatically after they are
+//! disconnected, such as ones which reboot themselves.
+
+use std::time::Duration;
+
+use futures_timer::Delay;
+
+use crate::timer::Deadline;
+use crate::usb::{ControlIn, ControlOut, Error, UsbDevice, UsbDeviceInfo, UsbInterface};
+use crate::{watch, Device, DeviceFilter, DeviceInfo, Interface};
+
+/// How long to wait before trying again when a device which was found
+/// couldn't be opened, such as one which is still going away.
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A device and one of its interfaces, which are found and opened again when
+/// the device is disconnected, so that devices which reboot themselves (for
+/// firmware updates or after a watchdog reset) don't need reconnecting by
+/// hand.
+///
+/// When a transfer fails with [`Error::Disconnected`], this waits for a
+/// device matching the same filters to be connected, opens it, claims the
+/// interface again and repeats the transfer. If no device comes back within
+/// the reconnect timeout, or it has to reconnect too many times for one
+/// transfer, the transfer fails with [`Error::Disconnected`].
+///
+/// Transfers which shouldn't be repeated, since the device may have acted on
+/// them before it was disconnected, can use the `_once` methods instead,
+/// which reconnect but then return the error.
+///
+/// **Note:** On WASM, only devices the page has already been given access to
+/// can be found again, so the device must have been picked with
+/// [`crate::get_device`] or [`crate::request_device`] first.
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use std::time::Duration;
+/// use cross_usb::{device_filter, ResilientDevice};
+///
+/// let filter = vec![device_filter!{vendor_id: 0x054c, product_id: 0x00c9}];
+/// let mut device = ResilientDevice::builder(filter, 0)
+///     .reconnect_timeout(Duration::from_secs(30))
+///     .open()
+///     .await
+///     .expect("Failed to open device");
+///
+/// // Tell the device to reboot, which shouldn't happen twice
+/// device.bulk_out_once(0x02, &[0xFF]).await.expect("Failed to reboot device");
+///
+/// // This waits for the device to come back if it has to
+/// let status = device.bulk_in(0x81, 64).await.expect("Failed to read status");
+/// # })
+/// ```
+#[derive(Debug)]
+pub struct ResilientDevice {
+    options: ResilientDeviceBuilder,
+    connection: Option<Connection>,
+}
+
+/// The open device and its claimed interface.
+#[derive(Debug)]
+struct Connection {
+    // The interface is declared first so that it is dropped before the
+    // device it belongs to
+    interface: Interface,
+    device: Device,
+}
+
+impl ResilientDevice {
+    /// Open the first connected device matching any of `device_filters`,
+    /// and claim the interface numbered `interface_number`, with the default
+    /// reconnect settings.
+    ///
+    /// Fails with [`Error::DeviceNotFound`] if no matching device is
+    /// connected.
+    pub async fn open(device_filters: Vec<DeviceFilter>, interface_number: u8) -> Result<Self, Error> {
+        Self::builder(device_filters, interface_number).open().await
+    }
+
+    /// Start setting up a device which opens the interface numbered
+    /// `interface_number` on the first connected device matching any of
+    /// `device_filters`.
+    pub fn builder(device_filters: Vec<DeviceFilter>, interface_number: u8) -> ResilientDeviceBuilder {
+        ResilientDeviceBuilder {
+            device_filters,
+            interface_number,
+            detach_kernel_driver: false,
+            reconnect_timeout: Duration::from_secs(10),
+            max_reconnects: 3,
+        }
+    }
+
+    /// The device which is currently open, or `None` if reconnecting to it
+    /// failed. This changes whenever the device is reconnected.
+    pub fn device(&self) -> Option<&Device> {
+        self.connection.as_ref().map(|connection| &connection.device)
+    }
+
+    /// The interface which is currently claimed, or `None` if reconnecting
+    /// to the device failed. Transfers made on it directly aren't repeated
+    /// when the device is disconnected. This changes whenever the device is
+    /// reconnected.
+    pub fn interface(&self) -> Option<&Interface> {
+        self.connection.as_ref().map(|connection| &connection.interface)
+    }
+
+    /// Close the device, then find it and open it again, without waiting for
+    /// a transfer to fail.
+    pub async fn reconnect(&mut self) -> Result<(), Error> {
+        // The old device is closed first, since it can't always be opened
+        // twice
+        self.connection = None;
+        self.connection = Some(self.options.reconnect().await?);
+
+        Ok(())
+    }
+
+    /// The claimed interface, reconnecting first if an earlier attempt to
+    /// reconnect failed.
+    async fn connected(&mut self) -> Result<&Interface, Error> {
+        let connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => self.options.reconnect().await?,
+        };
+
+        Ok(&self.connection.insert(connection).interface)
+    }
+
+    /// Reconnect after a transfer failed with [`Error::Disconnected`], unless
+    /// it has already reconnected as many times as it may.
+    async fn recover(&mut self, reconnects: &mut u32) -> Result<(), Error> {
+        if *reconnects >= self.options.max_reconnects {
+            return Err(Error::Disconnected);
+        }
+        *reconnects += 1;
+
+        self.reconnect().await
+    }
+
+    /// A control in transfer, as with [`UsbInterface::control_in`], which is
+    /// repeated after reconnecting if the device is disconnected.
+    pub async fn control_in(&mut self, data: ControlIn) -> Result<Vec<u8>, Error> {
+        let mut reconnects = 0;
+        loop {
+            let result = self.connected().await?.control_in(data).await;
+            match result {
+                Err(Error::Disconnected) => self.recover(&mut reconnects).await?,
+                result => return result,
+            }
+        }
+    }
+
+    /// A control out transfer, as with [`UsbInterface::control_out`], which
+    /// is repeated after reconnecting if the device is disconnected.
+    pub async fn control_out(&mut self, data: ControlOut<'_>) -> Result<usize, Error> {
+        let mut reconnects = 0;
+        loop {
+            let result = self.connected().await?.control_out(data).await;
+            match result {
+                Err(Error::Disconnected) => self.recover(&mut reconnects).await?,
+                result => return result,
+            }
+        }
+    }
+
+    /// A control out transfer which isn't repeated. If the device is
+    /// disconnected this reconnects, so the next transfer can go ahead, and
+    /// then fails with [`Error::Disconnected`].
+    pub async fn control_out_once(&mut self, data: ControlOut<'_>) -> Result<usize, Error> {
+        let result = self.connected().await?.control_out(data).await;
+        match result {
+            Err(Error::Disconnected) => {
+                self.recover(&mut 0).await?;
+                Err(Error::Disconnected)
+            }
+            result => result,
+        }
+    }
+
+    /// A bulk in transfer, as with [`UsbInterface::bulk_in`], which is
+    /// repeated after reconnecting if the device is disconnected.
+    pub async fn bulk_in(&mut self, endpoint: u8, length: usize) -> Result<Vec<u8>, Error> {
+        let mut reconnects = 0;
+        loop {
+            let result = self.connected().await?.bulk_in(endpoint, length).await;
+            match result {
+                Err(Error::Disconnected) => self.recover(&mut reconnects).await?,
+                result => return result,
+            }
+        }
+    }
+
+    /// A bulk out transfer, as with [`UsbInterface::bulk_out`], which is
+    /// repeated after reconnecting if the device is disconnected.
+    ///
+    /// Transfers which sent some of their data before failing are never
+    /// repeated, and fail with [`Error::PartialTransfer`].
+    pub async fn bulk_out(&mut self, endpoint: u8, data: &[u8]) -> Result<usize, Error> {
+        let mut reconnects = 0;
+        loop {
+            let result = self.connected().await?.bulk_out(endpoint, data).await;
+            match result {
+                Err(Error::Disconnected) => self.recover(&mut reconnects).await?,
+                result => return result,
+            }
+        }
+    }
+
+    /// A bulk out transfer which isn't repeated. If the device is
+    /// disconnected this reconnects, so the next transfer can go ahead, and
+    /// then fails with [`Error::Disconnected`].
+    pub async fn bulk_out_once(&mut self, endpoint: u8, data: &[u8]) -> Result<usize, Error> {
+        let result = self.connected().await?.bulk_out(endpoint, data).await;
+        match result {
+            Err(Error::Disconnected) => {
+                self.recover(&mut 0).await?;
+                Err(Error::Disconnected)
+            }
+            result => result,
+        }
+    }
+}
+
+/// Settings for a [`ResilientDevice`], from [`ResilientDevice::builder`].
+#[derive(Debug, Clone)]
+pub struct ResilientDeviceBuilder {
+    device_filters: Vec<DeviceFilter>,
+    interface_number: u8,
+    detach_kernel_driver: bool,
+    reconnect_timeout: Duration,
+    max_reconnects: u32,
+}
+
+impl ResilientDeviceBuilder {
+    /// Detach any kernel driver from the interface when claiming it, as with
+    /// [`UsbDevice::detach_and_open_interface`]. Defaults to `false`.
+    pub fn detach_kernel_driver(mut self, detach: bool) -> Self {
+        self.detach_kernel_driver = detach;
+        self
+    }
+
+    /// How long to wait for the device to come back after it's disconnected.
+    /// Defaults to 10 seconds.
+    pub fn reconnect_timeout(mut self, timeout: Duration) -> Self {
+        self.reconnect_timeout = timeout;
+        self
+    }
+
+    /// How many times the device may be reconnected during one transfer
+    /// before giving up. Defaults to 3.
+    pub fn max_reconnects(mut self, max_reconnects: u32) -> Self {
+        self.max_reconnects = max_reconnects;
+        self
+    }
+
+    /// Open the first connected matching device and claim the interface.
+    ///
+    /// Fails with [`Error::DeviceNotFound`] if no matching device is
+    /// connected.
+    pub async fn open(self) -> Result<ResilientDevice, Error> {
+        let Some(device_info) = watch::find_connected(&self.device_filters).await? else {
+            return Err(Error::DeviceNotFound);
+        };
+
+        let connection = self.connect(device_info).await?;
+
+        Ok(ResilientDevice {
+            options: self,
+            connection: Some(connection),
+        })
+    }
+
+    /// Wait for a matching device to be connected, and open it. Devices
+    /// which can't be opened yet are tried again until the timeout passes,
+    /// as the old device may still be listed for a moment after it is
+    /// disconnected.
+    async fn reconnect(&self) -> Result<Connection, Error> {
+        let reconnect = async {
+            loop {
+                let device_info = watch::wait_for_device(self.device_filters.clone(), None).await?;
+
+                match self.connect(device_info).await {
+                    Ok(connection) => return Ok(connection),
+                    Err(_) => Delay::new(RETRY_INTERVAL).await,
+                }
+            }
+        };
+
+        match Deadline::after(self.reconnect_timeout).run(reconnect).await {
+            Err(Error::Timeout) => Err(Error::Disconnected),
+            result => result,
+        }
+    }
+
+    async fn connect(&self, device_info: DeviceInfo) -> Result<Connection, Error> {
+        let device = device_info.open().await?;
+        let interface = if self.detach_kernel_driver {
+            device.detach_and_open_interface(self.interface_number).await?
+        } else {
+            device.open_interface(self.interface_number).await?
+        };
+
+        Ok(Connection { interface, device })
+    }
+}
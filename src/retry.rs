@@ -0,0 +1,143 @@
+//! Retrying transfers which fail with transient errors.
+
+use std::future::Future;
+use std::time::Duration;
+
+use futures_timer::Delay;
+
+use crate::timer::Deadline;
+use crate::usb::{Error, RetryPolicy};
+
+impl RetryPolicy {
+    /// Run `transfer` until it succeeds, fails with an error which shouldn't
+    /// be retried, or runs out of attempts.
+    pub(crate) async fn run<T, F, Fut>(&self, mut transfer: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        self.attempt(None, false, |_| transfer()).await
+    }
+
+    /// Run an OUT `transfer` as with [`RetryPolicy::run`]. One which times
+    /// out isn't retried, as its data may have been sent anyway.
+    pub(crate) async fn run_out<T, F, Fut>(&self, mut transfer: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        self.attempt(None, true, |_| transfer()).await
+    }
+
+    /// Run `transfer` as with [`RetryPolicy::run`], giving each attempt the
+    /// time left until `deadline`, and giving up once it has passed.
+    pub(crate) async fn run_until<T, F, Fut>(&self, deadline: &Deadline, transfer: F) -> Result<T, Error>
+    where
+        F: FnMut(Duration) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        self.attempt(Some(deadline), false, transfer).await
+    }
+
+    /// Run an OUT `transfer` as with [`RetryPolicy::run_until`], without
+    /// retrying it if it times out.
+    pub(crate) async fn run_out_until<T, F, Fut>(&self, deadline: &Deadline, transfer: F) -> Result<T, Error>
+    where
+        F: FnMut(Duration) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        self.attempt(Some(deadline), true, transfer).await
+    }
+
+    async fn attempt<T, F, Fut>(&self, deadline: Option<&Deadline>, out: bool, mut transfer: F) -> Result<T, Error>
+    where
+        F: FnMut(Duration) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let remaining = || deadline.map_or(Duration::MAX, Deadline::remaining);
+
+        let mut attempt = 1;
+        loop {
+            let err = match transfer(remaining()).await {
+                Err(err) if attempt < self.max_attempts && self.should_retry(&err, out) => err,
+                result => return result,
+            };
+            attempt += 1;
+
+            if !self.backoff.is_zero() {
+                Delay::new(self.backoff.min(remaining())).await;
+            }
+
+            if remaining().is_zero() {
+                return Err(err);
+            }
+        }
+    }
+
+    fn should_retry(&self, err: &Error, out: bool) -> bool {
+        match err {
+            Error::Stall
+            | Error::Disconnected
+            | Error::Cancelled
+            | Error::PartialTransfer { .. } => false,
+            // The data of an OUT transfer which timed out may still reach the
+            // device, so sending it again could send it twice
+            Error::Timeout if out => false,
+            err => (self.retry_on)(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn run_until_stops_retrying_at_the_deadline() {
+        let policy = RetryPolicy::new(100, Duration::from_millis(20));
+        let deadline = Deadline::after(Duration::from_millis(50));
+
+        let start = Instant::now();
+        let mut attempts = Vec::new();
+        let result: Result<(), Error> = futures::executor::block_on(policy.run_until(&deadline, |remaining| {
+            attempts.push(remaining);
+            async { Err(Error::TransferError) }
+        }));
+
+        assert_eq!(result, Err(Error::TransferError));
+        assert!(start.elapsed() < Duration::from_millis(200));
+        assert!((2..=4).contains(&attempts.len()), "{attempts:?}");
+
+        // Later attempts only get what's left of the time
+        assert!(attempts.windows(2).all(|pair| pair[1] < pair[0]), "{attempts:?}");
+    }
+
+    #[test]
+    fn out_transfers_are_not_retried_after_a_timeout() {
+        let policy = RetryPolicy::new(3, Duration::ZERO);
+
+        let mut attempts = 0;
+        let result: Result<(), Error> = futures::executor::block_on(policy.run_out(|| {
+            attempts += 1;
+            async { Err(Error::Timeout) }
+        }));
+        assert_eq!(result, Err(Error::Timeout));
+        assert_eq!(attempts, 1);
+
+        // Other errors, and IN transfers, are still retried
+        let mut attempts = 0;
+        let _: Result<(), Error> = futures::executor::block_on(policy.run_out(|| {
+            attempts += 1;
+            async { Err(Error::TransferError) }
+        }));
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let _: Result<(), Error> = futures::executor::block_on(policy.run(|| {
+            attempts += 1;
+            async { Err(Error::Timeout) }
+        }));
+        assert_eq!(attempts, 3);
+    }
+}
@@ -0,0 +1,176 @@
+//! The standard GET_STATUS request, for the device, an interface or an
+//! endpoint, and the standard features which change the status.
+
+use crate::usb::{
+    ControlIn, ControlOut, ControlType, DeviceStatus, EndpointStatus, Error, InterfaceStatus,
+    Recipient, UsbInterface,
+};
+use crate::{Device, Interface};
+
+const REQUEST_GET_STATUS: u8 = 0x00;
+const REQUEST_CLEAR_FEATURE: u8 = 0x01;
+const REQUEST_SET_FEATURE: u8 = 0x03;
+
+const FEATURE_DEVICE_REMOTE_WAKEUP: u16 = 1;
+
+/// A GET_STATUS request for `recipient`. Interfaces and endpoints are chosen
+/// by their number or address in the low byte of the index.
+fn get_status(recipient: Recipient, index: u8) -> ControlIn {
+    ControlIn {
+        control_type: ControlType::Standard,
+        recipient,
+        request: REQUEST_GET_STATUS,
+        value: 0,
+        index: index as u16,
+        length: 2,
+    }
+}
+
+/// The status bits from the reply to a GET_STATUS request.
+fn status_bits(reply: &[u8]) -> Result<u16, Error> {
+    match *reply {
+        [low, high, ..] => Ok(u16::from_le_bytes([low, high])),
+        _ => Err(Error::TransferError),
+    }
+}
+
+impl Device {
+    /// Read the status of the device with a GET_STATUS request.
+    ///
+    /// **Note:** On Windows, this always fails because the OS only allows
+    /// control transfers through a claimed interface.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// let status = device.status().await.expect("Failed to read status");
+    /// println!("Remote wakeup enabled: {}", status.remote_wakeup);
+    /// # })
+    /// ```
+    pub async fn status(&self) -> Result<DeviceStatus, Error> {
+        let bits = status_bits(&self.control_in(get_status(Recipient::Device, 0)).await?)?;
+
+        Ok(DeviceStatus {
+            self_powered: bits & 0x01 != 0,
+            remote_wakeup: bits & 0x02 != 0,
+        })
+    }
+
+    /// Allow or forbid the device to wake the host, with the standard
+    /// DEVICE_REMOTE_WAKEUP feature. The current state is in
+    /// [`DeviceStatus::remote_wakeup`].
+    ///
+    /// Devices which don't support remote wakeup stall the request, which
+    /// fails with [`Error::Stall`].
+    ///
+    /// **Note:** On Windows, this always fails because the OS only allows
+    /// control transfers through a claimed interface.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// device.set_remote_wakeup(true).await.expect("Remote wakeup is not supported");
+    /// assert!(device.status().await.unwrap().remote_wakeup);
+    /// # })
+    /// ```
+    pub async fn set_remote_wakeup(&self, enabled: bool) -> Result<(), Error> {
+        let request = match enabled {
+            true => REQUEST_SET_FEATURE,
+            false => REQUEST_CLEAR_FEATURE,
+        };
+
+        self.control_out(ControlOut {
+            control_type: ControlType::Standard,
+            recipient: Recipient::Device,
+            request,
+            value: FEATURE_DEVICE_REMOTE_WAKEUP,
+            index: 0,
+            data: &[],
+        })
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl Interface {
+    /// Read the status of this interface with a GET_STATUS request.
+    pub async fn status(&self) -> Result<InterfaceStatus, Error> {
+        let request = get_status(Recipient::Interface, self.interface_number());
+        let bits = status_bits(&self.control_in(request).await?)?;
+
+        Ok(InterfaceStatus {
+            remote_wake_capable: bits & 0x01 != 0,
+            remote_wakeup: bits & 0x02 != 0,
+        })
+    }
+
+    /// Read the status of the endpoint with the address `endpoint` with a
+    /// GET_STATUS request, such as whether it is halted.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// let interface = device.open_interface(0).await.expect("Failed to open interface");
+    ///
+    /// if interface.endpoint_status(0x81).await.expect("Failed to read status").halted {
+    ///     interface.clear_halt(0x81).await.expect("Failed to clear halt");
+    /// }
+    /// # })
+    /// ```
+    pub async fn endpoint_status(&self, endpoint: u8) -> Result<EndpointStatus, Error> {
+        let bits = status_bits(&self.control_in(get_status(Recipient::Endpoint, endpoint)).await?)?;
+
+        Ok(EndpointStatus {
+            halted: bits & 0x01 != 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_status_for_endpoint() {
+        let request = get_status(Recipient::Endpoint, 0x81);
+
+        assert!(matches!(request.control_type, ControlType::Standard));
+        assert!(matches!(request.recipient, Recipient::Endpoint));
+        assert_eq!(request.request, 0);
+        assert_eq!(request.value, 0);
+        assert_eq!(request.index, 0x0081);
+        assert_eq!(request.length, 2);
+    }
+
+    #[test]
+    fn get_status_for_interface() {
+        let request = get_status(Recipient::Interface, 3);
+
+        assert!(matches!(request.recipient, Recipient::Interface));
+        assert_eq!(request.index, 0x0003);
+    }
+
+    #[test]
+    fn status_bits_are_little_endian() {
+        assert_eq!(status_bits(&[0x01, 0x00]), Ok(0x0001));
+        assert_eq!(status_bits(&[0x03, 0x80]), Ok(0x8003));
+
+        // Anything past the two status bytes is ignored
+        assert_eq!(status_bits(&[0x02, 0x00, 0xff]), Ok(0x0002));
+    }
+
+    #[test]
+    fn status_bits_of_short_reply() {
+        assert_eq!(status_bits(&[]), Err(Error::TransferError));
+        assert_eq!(status_bits(&[0x01]), Err(Error::TransferError));
+    }
+}
@@ -0,0 +1,79 @@
+//! Timers which work the same on native and WASM targets.
+//!
+//! On native targets these are driven by a background thread, and on WASM
+//! they use `setTimeout`, so they don't depend on any particular async runtime.
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::Poll;
+use std::time::Duration;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::OnceLock;
+#[cfg(not(target_family = "wasm"))]
+use std::time::Instant;
+
+use futures_timer::Delay;
+
+use crate::usb::Error;
+
+/// The time since an arbitrary point, which only matters when comparing two
+/// of these.
+#[cfg(not(target_family = "wasm"))]
+fn now() -> Duration {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed()
+}
+
+// `std::time::Instant` panics on WASM, so the browser's clock is used instead
+#[cfg(target_family = "wasm")]
+fn now() -> Duration {
+    Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+}
+
+/// A point in time which one or more operations must finish before.
+pub(crate) struct Deadline {
+    delay: Delay,
+    end: Duration,
+}
+
+impl Deadline {
+    /// A deadline which passes after `duration`.
+    pub(crate) fn after(duration: Duration) -> Self {
+        Self {
+            delay: Delay::new(duration),
+            end: now() + duration,
+        }
+    }
+
+    /// The time left until the deadline passes, which is zero once it has.
+    pub(crate) fn remaining(&self) -> Duration {
+        self.end.saturating_sub(now())
+    }
+
+    /// Run `future` until it completes, or fail with [`Error::Timeout`] if
+    /// the deadline passes first. The future is dropped on timeout.
+    pub(crate) async fn run<T>(&mut self, future: impl Future<Output = Result<T, Error>>) -> Result<T, Error> {
+        let mut future = pin!(future);
+
+        std::future::poll_fn(|cx| {
+            if let Poll::Ready(result) = future.as_mut().poll(cx) {
+                return Poll::Ready(result);
+            }
+
+            match pin!(&mut self.delay).poll(cx) {
+                Poll::Ready(()) => Poll::Ready(Err(Error::Timeout)),
+                Poll::Pending => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+/// Run `future`, failing with [`Error::Timeout`] if `timeout` is set and
+/// passes before it completes.
+pub(crate) async fn bounded<T>(timeout: Option<Duration>, future: impl Future<Output = Result<T, Error>>) -> Result<T, Error> {
+    match timeout {
+        Some(timeout) => Deadline::after(timeout).run(future).await,
+        None => future.await,
+    }
+}
@@ -0,0 +1,90 @@
+//! Higher level transfers which are built on top of [`UsbInterface`], and so
+//! work the same way on every target.
+
+use std::time::Duration;
+
+use crate::timer::Deadline;
+use crate::usb::{Error, TransactionStage, UsbInterface};
+use crate::Interface;
+
+impl Interface {
+    /// Send a command on a bulk OUT endpoint, and then read the reply from a
+    /// bulk IN endpoint.
+    ///
+    /// The read is only submitted after the write has completed, and
+    /// `timeout` applies to the whole exchange, including any retries from
+    /// the [`crate::usb::RetryPolicy`] of the interface. If either half fails, the
+    /// error is an [`Error::Transaction`] containing the [`TransactionStage`]
+    /// which failed, so a failed write can be told apart from a read which
+    /// timed out.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// use std::time::Duration;
+    ///
+    /// let interface = device.open_interface(0).await.expect("Failed to open interface");
+    ///
+    /// let reply = interface
+    ///     .bulk_transaction(0x02, 0x81, &[0x01, 0x00], 64, Duration::from_secs(1))
+    ///     .await
+    ///     .expect("Transaction failed");
+    /// # })
+    /// ```
+    pub async fn bulk_transaction(
+        &self,
+        out_endpoint: u8,
+        in_endpoint: u8,
+        command: &[u8],
+        response_length: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, Error> {
+        // Each attempt is given the time that's left rather than being
+        // dropped when the deadline passes, so the endpoint is cleaned up
+        // after a timeout the same way as for any other transfer
+        let deadline = Deadline::after(timeout);
+
+        self.bulk_out_until(out_endpoint, command, &deadline)
+            .await
+            .map_err(|err| Error::Transaction {
+                stage: TransactionStage::Write,
+                source: Box::new(err),
+            })?;
+
+        self.bulk_in_until(in_endpoint, response_length, &deadline)
+            .await
+            .map_err(|err| Error::Transaction {
+                stage: TransactionStage::Read,
+                source: Box::new(err),
+            })
+    }
+
+    /// Send several buffers to a bulk OUT `endpoint` as a single transfer, as
+    /// though they had been joined together first. Returns the total number
+    /// of bytes written.
+    ///
+    /// The buffers are copied into one transfer, so the device either receives
+    /// all of them or the transfer fails. If it fails after some bytes were
+    /// sent, the error is an [`Error::PartialTransfer`].
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// # let payload: Vec<u8> = vec![];
+    /// let interface = device.open_interface(0).await.expect("Failed to open interface");
+    ///
+    /// let header = (payload.len() as u32).to_le_bytes();
+    /// interface
+    ///     .bulk_out_vectored(0x02, &[&header, &payload])
+    ///     .await
+    ///     .expect("Failed to send packet");
+    /// # })
+    /// ```
+    pub async fn bulk_out_vectored(&self, endpoint: u8, bufs: &[&[u8]]) -> Result<usize, Error> {
+        self.bulk_out_owned(endpoint, bufs.concat()).await
+    }
+}
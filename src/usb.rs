@@ -2,8 +2,26 @@
 //! This module contains the traits and associated functions and
 //! structs which allow for USB communication.
 
+use std::time::Duration;
+
 use thiserror::Error;
 
+/// A bound which is [`Send`] on native targets, and nothing on WASM where
+/// everything runs on a single thread.
+#[cfg(not(target_family = "wasm"))]
+pub trait MaybeSend: Send {}
+
+#[cfg(not(target_family = "wasm"))]
+impl<T: Send> MaybeSend for T {}
+
+/// A bound which is [`Send`] on native targets, and nothing on WASM where
+/// everything runs on a single thread.
+#[cfg(target_family = "wasm")]
+pub trait MaybeSend {}
+
+#[cfg(target_family = "wasm")]
+impl<T> MaybeSend for T {}
+
 /// Information about a USB device before claiming it.
 pub trait UsbDeviceInfo {
     /// A unique USB Device
@@ -24,6 +42,9 @@ pub trait UsbDeviceInfo {
     /// Device standard subclass
     async fn subclass(&self) -> u8;
 
+    /// Device standard protocol
+    async fn protocol(&self) -> u8;
+
     /// Get the manufacturer string string of the device, if available without device IO
     ///
     /// Not available on Windows
@@ -31,6 +52,69 @@ pub trait UsbDeviceInfo {
 
     /// Get the product string of the device, if available without device IO
     async fn product_string(&self) -> Option<String>;
+
+    /// Get the serial number string of the device, if available without
+    /// device IO
+    async fn serial_number_string(&self) -> Option<String>;
+
+    /// The release number of the device (`bcdDevice`), which is usually its
+    /// firmware version.
+    async fn device_version(&self) -> Version;
+
+    /// The version of the USB specification the device claims to support
+    /// (`bcdUSB`), if available without device IO.
+    ///
+    /// This is not the speed the device is actually connected at.
+    ///
+    /// **Note:** On native targets this is only available on Linux.
+    async fn usb_version(&self) -> Option<Version>;
+
+    /// The speed the device is connected at, if known.
+    ///
+    /// **Note:** WebUSB has no way to find this, so it is always `None` on WASM.
+    async fn speed(&self) -> Option<Speed>;
+
+    /// The number of the bus the device is connected to, as shown by tools
+    /// like `lsusb`.
+    ///
+    /// **Note:** This is always `None` on WASM.
+    async fn bus_number(&self) -> Option<u8>;
+
+    /// The address of the device on its bus, as shown by tools like `lsusb`.
+    ///
+    /// **Note:** This is always `None` on WASM.
+    async fn device_address(&self) -> Option<u8>;
+
+    /// The port numbers on each hub between the root hub and the device,
+    /// starting from the root hub. This stays the same when a device is
+    /// reconnected to the same physical port, so it can be used to tell
+    /// identical devices apart.
+    ///
+    /// **Note:** This is only available on Linux and macOS, and is always
+    /// `None` on Windows and WASM.
+    async fn port_chain(&self) -> Option<Vec<u8>>;
+
+    /// A platform specific string which identifies the device to the
+    /// operating system, for passing to other tools:
+    /// * On Linux, the sysfs path of the device.
+    /// * On Windows, the device instance ID.
+    /// * On macOS, the location ID as a hexadecimal number.
+    ///
+    /// On Linux and macOS this stays the same when a device is reconnected to
+    /// the same physical port. On Windows it also does for devices without a
+    /// serial number, otherwise it follows the device between ports.
+    ///
+    /// **Note:** This is always `None` on WASM.
+    async fn platform_path(&self) -> Option<String>;
+
+    /// The interfaces of the active configuration, as reported by the OS
+    /// without opening the device. This makes it possible to find the right
+    /// device by its interface classes without claiming anything.
+    ///
+    /// **Note:** This is always empty on WASM, where devices can't be
+    /// inspected before they are opened. Use [`crate::Device::interfaces`]
+    /// after opening instead.
+    async fn interfaces(&self) -> Vec<InterfaceFilterInfo>;
 }
 
 /// A unique USB device.
@@ -41,6 +125,10 @@ pub trait UsbDevice {
     type Interface;
 
     /// Open a specific interface of the device
+    ///
+    /// **Note:** On WASM, if the browser left the device unconfigured, its
+    /// first configuration is selected before the interface is claimed. Use
+    /// [`UsbDevice::set_configuration`] beforehand to choose a different one.
     async fn open_interface(&self, number: u8) -> Result<Self::Interface, Error>;
 
     /// Open a specific interface of the device, detaching any
@@ -49,6 +137,31 @@ pub trait UsbDevice {
     /// **Note:** This only has an effect on Native, and only on Linux.
     async fn detach_and_open_interface(&self, number: u8) -> Result<Self::Interface, Error>;
 
+    /// The value of the active configuration (`bConfigurationValue`), or
+    /// `None` if the device is unconfigured.
+    ///
+    /// Some operating systems leave composite devices unconfigured, in which
+    /// case a configuration has to be selected before any interface can be
+    /// opened.
+    async fn active_configuration(&self) -> Result<Option<u8>, Error>;
+
+    /// Select the configuration whose `bConfigurationValue` is `value`.
+    ///
+    /// This does nothing if that configuration is already active. Otherwise
+    /// every interface of the device must have been dropped first, or it
+    /// fails with [`Error::InterfacesClaimed`].
+    async fn set_configuration(&self, value: u8) -> Result<(), Error>;
+
+    /// The name of the kernel driver bound to `interface`, such as
+    /// `"cdc_acm"`, or `None` if no driver is bound to it.
+    ///
+    /// This is the driver which [`UsbDevice::detach_and_open_interface`]
+    /// would detach.
+    ///
+    /// **Note:** This is only available on Linux, and is always `None` on
+    /// other platforms and WASM.
+    async fn kernel_driver(&self, interface: u8) -> Result<Option<String>, Error>;
+
     /// Reset the device, which causes it to no longer be usable. You must
     /// request a new device with [crate::get_device]
     async fn reset(&self) -> Result<(), Error>;
@@ -58,6 +171,27 @@ pub trait UsbDevice {
     /// **Note:** On Native this simply resets the device.
     async fn forget(&self) -> Result<(), Error>;
 
+    /// Whether the device can still be used, without communicating with it.
+    ///
+    /// This is `false` after [`UsbDevice::reset`] or [`UsbDevice::forget`],
+    /// and once the device has been unplugged.
+    ///
+    /// **Note:** On WASM this is `USBDevice.opened`, so it is also `false`
+    /// after the page closes the device some other way.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// if !device.is_open().await {
+    ///     // Reconnect to the device
+    ///     let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// }
+    /// # })
+    /// ```
+    async fn is_open(&self) -> bool;
+
     /// 16 bit device Product ID
     async fn product_id(&self) -> u16;
 
@@ -70,13 +204,77 @@ pub trait UsbDevice {
     /// Device standard subclass
     async fn subclass(&self) -> u8;
 
-    /// Get the manufacturer string string of the device, if available without device IO
+    /// Device standard protocol
+    async fn protocol(&self) -> u8;
+
+    /// Get the manufacturer string of the device.
     ///
-    /// Not available on Windows
+    /// If the operating system hasn't cached it, it is read from the device's
+    /// string descriptors. Returns `None` if the device has no manufacturer
+    /// string or it couldn't be read.
     async fn manufacturer_string(&self) -> Option<String>;
 
-    /// Get the product string of the device, if available without device IO
+    /// Get the product string of the device.
+    ///
+    /// If the operating system hasn't cached it, it is read from the device's
+    /// string descriptors. Returns `None` if the device has no product string
+    /// or it couldn't be read.
     async fn product_string(&self) -> Option<String>;
+
+    /// Get the serial number string of the device.
+    ///
+    /// If the operating system hasn't cached it, it is read from the device's
+    /// string descriptors. Returns `None` if the device has no serial number
+    /// or it couldn't be read.
+    async fn serial_number_string(&self) -> Option<String>;
+
+    /// The release number of the device (`bcdDevice`), which is usually its
+    /// firmware version.
+    async fn device_version(&self) -> Version;
+
+    /// The version of the USB specification the device claims to support
+    /// (`bcdUSB`). Returns `None` if it couldn't be read from the device.
+    ///
+    /// This is not the speed the device is actually connected at.
+    async fn usb_version(&self) -> Option<Version>;
+
+    /// The speed the device is connected at, if known.
+    ///
+    /// **Note:** WebUSB has no way to find this, so it is always `None` on WASM.
+    async fn speed(&self) -> Option<Speed>;
+
+    /// The number of the bus the device is connected to, as shown by tools
+    /// like `lsusb`.
+    ///
+    /// **Note:** This is always `None` on WASM.
+    async fn bus_number(&self) -> Option<u8>;
+
+    /// The address of the device on its bus, as shown by tools like `lsusb`.
+    ///
+    /// **Note:** This is always `None` on WASM.
+    async fn device_address(&self) -> Option<u8>;
+
+    /// The port numbers on each hub between the root hub and the device,
+    /// starting from the root hub. This stays the same when a device is
+    /// reconnected to the same physical port, so it can be used to tell
+    /// identical devices apart.
+    ///
+    /// **Note:** This is only available on Linux and macOS, and is always
+    /// `None` on Windows and WASM.
+    async fn port_chain(&self) -> Option<Vec<u8>>;
+
+    /// A platform specific string which identifies the device to the
+    /// operating system, for passing to other tools:
+    /// * On Linux, the sysfs path of the device.
+    /// * On Windows, the device instance ID.
+    /// * On macOS, the location ID as a hexadecimal number.
+    ///
+    /// On Linux and macOS this stays the same when a device is reconnected to
+    /// the same physical port. On Windows it also does for devices without a
+    /// serial number, otherwise it follows the device between ports.
+    ///
+    /// **Note:** This is always `None` on WASM.
+    async fn platform_path(&self) -> Option<String>;
 }
 
 /// A specific interface of a USB device
@@ -96,8 +294,249 @@ pub trait UsbInterface<'a> {
     /// A USB bulk out transfer (host to device).
     /// It takes in a bulk endpoint to send to along with some data as
     /// a slice, and returns a [Result] containing the number of bytes transferred
+    ///
+    /// If the transfer fails after some of the data was sent, the error is an
+    /// [`Error::PartialTransfer`] holding the number of bytes written.
     async fn bulk_out(&self, endpoint: u8, data: &[u8]) -> Result<usize, Error>;
 
+    /// A USB bulk out transfer (host to device) like [`UsbInterface::bulk_out`],
+    /// which takes ownership of `data` instead of copying it.
+    ///
+    /// On native targets the buffer is handed straight to the platform,
+    /// unless a [`RetryPolicy`] is set which needs a copy for each attempt.
+    ///
+    /// **Note:** On WASM the data is always copied into the browser.
+    async fn bulk_out_owned(&self, endpoint: u8, data: Vec<u8>) -> Result<usize, Error>;
+
+    /// A USB bulk in transfer (device to host) like [`UsbInterface::bulk_in`],
+    /// which replaces the contents of `buf` with the received bytes instead of
+    /// allocating a new buffer. Returns the number of bytes received.
+    ///
+    /// On native targets the allocation of `buf` is handed to the platform
+    /// and reused, unless a [`RetryPolicy`] is set. If the transfer fails,
+    /// `buf` is left empty.
+    async fn bulk_in_into(&self, endpoint: u8, length: usize, buf: &mut Vec<u8>) -> Result<usize, Error>;
+
+    /// A USB bulk in transfer (device to host) like [`UsbInterface::bulk_in`],
+    /// which also reports how the transfer ended.
+    ///
+    /// A transfer which returns fewer than `length` bytes ended on a short
+    /// packet, which many protocols use to mark the end of a message.
+    ///
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// use cross_usb::prelude::*;
+    ///
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// # let interface = device.open_interface(0).await.unwrap();
+    /// let mut record = Vec::new();
+    /// loop {
+    ///     let result = interface.bulk_in_result(0x81, 512).await?;
+    ///     record.extend_from_slice(&result.data);
+    ///
+    ///     if result.short_packet {
+    ///         break;
+    ///     }
+    /// }
+    /// # Ok::<(), cross_usb::usb::Error>(())
+    /// # });
+    /// ```
+    async fn bulk_in_result(&self, endpoint: u8, length: usize) -> Result<TransferResult, Error>;
+
+    /// A USB control in transfer (device to host) which fails with
+    /// [`Error::Timeout`] if it does not complete within `timeout`.
+    async fn control_in_timeout(&self, data: ControlIn, timeout: Duration) -> Result<Vec<u8>, Error>;
+
+    /// A USB control out transfer (host to device) which fails with
+    /// [`Error::Timeout`] if it does not complete within `timeout`.
+    async fn control_out_timeout(&self, data: ControlOut<'a>, timeout: Duration) -> Result<usize, Error>;
+
+    /// A USB bulk in transfer (device to host) which fails with
+    /// [`Error::Timeout`] if it does not complete within `timeout`.
+    ///
+    /// On native targets the transfer is cancelled when it times out, so the
+    /// buffer will not be written to afterwards.
+    ///
+    /// **Note:** On WASM, WebUSB has no way to abort a transfer, so the
+    /// transfer keeps running in the browser and its result is discarded.
+    /// The endpoint is reset with a clear halt request after a timeout so
+    /// later transfers are not out of step with the device. See
+    /// [`UsbInterface::can_abort_transfers`].
+    async fn bulk_in_timeout(&self, endpoint: u8, length: usize, timeout: Duration) -> Result<Vec<u8>, Error>;
+
+    /// A USB bulk out transfer (host to device) which fails with
+    /// [`Error::Timeout`] if it does not complete within `timeout`.
+    ///
+    /// On native targets the transfer is cancelled when it times out. If some
+    /// of the data was sent before then, the error is an
+    /// [`Error::PartialTransfer`] holding the number of bytes written, with
+    /// [`Error::Timeout`] as its source.
+    ///
+    /// **Note:** On WASM, WebUSB has no way to abort a transfer, so the
+    /// transfer keeps running in the browser and its result is discarded.
+    /// The endpoint is reset with a clear halt request after a timeout. See
+    /// [`UsbInterface::can_abort_transfers`].
+    async fn bulk_out_timeout(&self, endpoint: u8, data: &[u8], timeout: Duration) -> Result<usize, Error>;
+
+    /// Set a timeout which bounds every subsequent transfer on this interface
+    /// that doesn't have its own, failing it with [`Error::Timeout`].
+    ///
+    /// `None` (the default) waits forever. The `_timeout` variants of each
+    /// transfer always use their own timeout instead of this one.
+    ///
+    /// Clones of this interface made afterwards keep the same default timeout.
+    fn set_default_timeout(&mut self, timeout: Option<Duration>);
+
+    /// The timeout set with [`UsbInterface::set_default_timeout`], if any.
+    fn default_timeout(&self) -> Option<Duration>;
+
+    /// Clear the halt (stall) condition of a bulk or interrupt endpoint,
+    /// resetting its data toggle on both the host and the device.
+    ///
+    /// Use this after a transfer fails with [`Error::Stall`] to resume using
+    /// the endpoint. It should not be called while transfers are pending on
+    /// the endpoint.
+    ///
+    /// `endpoint` is the endpoint address, including the direction bit (for
+    /// example `0x81` for IN endpoint 1).
+    ///
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// use cross_usb::prelude::*;
+    /// use cross_usb::usb::Error;
+    ///
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// # let interface = device.open_interface(0).await.unwrap();
+    /// let reply = match interface.bulk_in(0x81, 64).await {
+    ///     Err(Error::Stall) => {
+    ///         interface.clear_halt(0x81).await?;
+    ///         interface.bulk_in(0x81, 64).await?
+    ///     }
+    ///     result => result?,
+    /// };
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    async fn clear_halt(&self, endpoint: u8) -> Result<(), Error>;
+
+    /// The number of this interface (`bInterfaceNumber`), which class
+    /// requests addressed to the interface put in their `index`.
+    fn interface_number(&self) -> u8;
+
+    /// The alternate settings of this interface in the active configuration,
+    /// in the order the device describes them.
+    fn alt_settings(&self) -> Vec<AltSettingInfo>;
+
+    /// The endpoints of the selected alternate setting of this interface,
+    /// not counting endpoint 0.
+    ///
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// use cross_usb::usb::{Direction, TransferType};
+    ///
+    /// let interface = device.open_interface(0).await.unwrap();
+    ///
+    /// // Find the first bulk IN endpoint instead of hardcoding it
+    /// let endpoint = interface
+    ///     .endpoints()
+    ///     .into_iter()
+    ///     .find(|ep| ep.direction == Direction::In && ep.transfer_type == TransferType::Bulk)
+    ///     .expect("No bulk IN endpoint");
+    ///
+    /// let data = interface.bulk_in(endpoint.address, endpoint.max_packet_size).await;
+    /// # });
+    /// ```
+    fn endpoints(&self) -> Vec<EndpointInfo>;
+
+    /// The string describing the selected alternate setting of this interface
+    /// (`iInterface`), such as `"DFU"`, or `None` if it doesn't have one.
+    ///
+    /// On native this reads the string descriptor in the first language the
+    /// device supports. On WASM the browser reads it when the device is
+    /// opened.
+    async fn interface_string(&self) -> Result<Option<String>, Error>;
+
+    /// Select an alternate setting of this interface, by its
+    /// `bAlternateSetting`. Some devices only expose their endpoints in an
+    /// alternate setting other than the default one.
+    ///
+    /// This affects every clone of the interface, and transfers which are
+    /// still in progress may fail.
+    ///
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// let interface = device.open_interface(1).await.unwrap();
+    ///
+    /// // Switch to the setting which has the streaming endpoints
+    /// if interface.alt_settings().iter().any(|setting| setting.alt_setting == 1) {
+    ///     interface.set_alt_setting(1).await.expect("Failed to select alternate setting");
+    /// }
+    /// # });
+    /// ```
+    async fn set_alt_setting(&self, alt_setting: u8) -> Result<(), Error>;
+
+    /// Set the [`RetryPolicy`] used to retry transfers on this interface which
+    /// fail with transient errors. By default transfers are not retried.
+    ///
+    /// Each attempt gets its own timeout, so a transfer with a timeout of one
+    /// second and three attempts may take more than three seconds to fail.
+    ///
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// use std::time::Duration;
+    /// use cross_usb::prelude::*;
+    /// use cross_usb::usb::RetryPolicy;
+    ///
+    /// # let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    /// let mut interface = device.open_interface(0).await.unwrap();
+    /// interface.set_default_timeout(Some(Duration::from_millis(500)));
+    /// interface.set_retry_policy(RetryPolicy::new(3, Duration::from_millis(50)));
+    ///
+    /// // Retried up to twice if it times out or fails with a transfer error
+    /// let reply = interface.bulk_in(0x81, 64).await;
+    /// # });
+    /// ```
+    fn set_retry_policy(&mut self, policy: RetryPolicy);
+
+    /// The [`RetryPolicy`] set with [`UsbInterface::set_retry_policy`].
+    fn retry_policy(&self) -> RetryPolicy;
+
+    /// Set the number of transfers kept in flight at once when streaming from
+    /// the bulk IN `endpoint`, such as by [`crate::io::BulkReader`]s created
+    /// afterwards. This is always at least 1, which is the default.
+    ///
+    /// Data is always returned in the order the transfers were submitted.
+    ///
+    /// **Note:** WebUSB has no way to queue transfers ahead of time, so on
+    /// WASM this has no effect and one transfer is in flight at a time.
+    fn set_in_flight(&mut self, endpoint: u8, depth: usize);
+
+    /// The number of transfers kept in flight when streaming from `endpoint`,
+    /// set with [`UsbInterface::set_in_flight`].
+    fn in_flight(&self, endpoint: u8) -> usize;
+
+    /// Cancel every transfer which is currently in progress on this interface,
+    /// causing them to return [`Error::Cancelled`]. Transfers started after
+    /// this is called are unaffected.
+    ///
+    /// Clones of an interface (including the adapters in [`crate::io`]) share
+    /// their transfers, so this cancels transfers made through any of them.
+    ///
+    /// **Note:** On WASM, WebUSB has no way to abort a transfer, so the
+    /// transfer keeps running in the browser and its result is discarded.
+    fn cancel_all(&self);
+
+    /// Whether a transfer which times out or is cancelled is actually
+    /// aborted, rather than being left to finish with its result discarded.
+    ///
+    /// This is `true` on native targets. WebUSB has no way to abort a
+    /// transfer, so it is `false` on WASM and timeouts there are best-effort.
+    fn can_abort_transfers(&self) -> bool;
+
     /* TODO: Figure out interrupt transfers on Web USB
     /// A USB interrupt in transfer (device to host).
     /// Takes in an endpoint and a buffer to fill
@@ -132,9 +571,1043 @@ pub enum Error {
     /// reconnected to.
     #[error("device no longer valid")]
     Invalid,
+
+    /// The endpoint stalled, which devices use to reject a request. Bulk and
+    /// interrupt endpoints stay halted until [`UsbInterface::clear_halt`] is
+    /// called.
+    #[error("endpoint stalled")]
+    Stall,
+
+    /// The device sent more data than was requested.
+    ///
+    /// **Note:** This is only reported on WASM. Native platforms report
+    /// this as an [`Error::TransferError`] instead.
+    #[error("device sent more data than requested")]
+    Babble,
+
+    /// The transfer was cancelled before it completed.
+    #[error("transfer cancelled")]
+    Cancelled,
+
+    /// The operation did not complete before its timeout.
+    #[error("operation timed out")]
+    Timeout,
+
+    /// An OUT transfer failed after some of its data had already been sent.
+    #[error("transfer failed after {written} bytes were written")]
+    PartialTransfer {
+        /// The number of bytes which were sent before it failed.
+        written: usize,
+
+        /// The error which caused it to fail.
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// Reading the data to send from its source failed, in
+    /// [`crate::Interface::send_from`].
+    #[error("reading from source failed: {message}")]
+    Read {
+        /// The kind of the I/O error returned by the source.
+        kind: std::io::ErrorKind,
+
+        /// The message of the I/O error returned by the source.
+        message: String,
+    },
+
+    /// The device returned a descriptor which is malformed or truncated.
+    #[error("invalid descriptor")]
+    InvalidDescriptor,
+
+    /// One half of a [`crate::Interface::bulk_transaction`] failed.
+    #[error("transaction failed during {stage}")]
+    Transaction {
+        /// The half of the transaction which failed.
+        stage: TransactionStage,
+
+        /// The error which caused it to fail.
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// The operation can't be performed while interfaces of the device are
+    /// claimed, such as [`UsbDevice::set_configuration`]. Drop every open
+    /// [`UsbInterface`] first.
+    #[error("interfaces of the device are still claimed")]
+    InterfacesClaimed,
+
+    /// The interface has no endpoint with this address in its selected
+    /// alternate setting.
+    #[error("endpoint {0:#04x} not found on this interface")]
+    EndpointNotFound(u8),
+
+    /// More than one device matches a [`DeviceId`], so it isn't clear which
+    /// one to open.
+    #[error("more than one device matches")]
+    AmbiguousDevice,
+
+    /// The interface is already claimed, by another program or by a kernel
+    /// driver. It may become available later.
+    #[error("interface is in use")]
+    Busy {
+        /// The kernel driver bound to the interface, if it's a driver rather
+        /// than a program which holds it. This is only known on Linux.
+        driver: Option<String>,
+    },
+
+    /// No interface of the active configuration matches the requested class.
+    #[error("no interface matches")]
+    InterfaceNotFound,
+
+    /// More than one interface of the active configuration matches the
+    /// requested class, so it isn't clear which one to open.
+    #[error("more than one interface matches: {0:?}")]
+    AmbiguousInterface(
+        /// The numbers of the interfaces which match.
+        Vec<u8>,
+    ),
+
+    /// The browser refused to ask the user for a device, because
+    /// [`crate::request_device`] wasn't called in response to a user gesture
+    /// such as a click.
+    ///
+    /// **Note:** This is only reported on WASM.
+    #[error("the browser didn't allow asking for a device")]
+    PermissionDenied,
+
+    /// The browser doesn't support WebUSB, or the page isn't in a secure
+    /// context (served over HTTPS or from `localhost`). Check for this ahead
+    /// of time with [`crate::is_supported`].
+    ///
+    /// **Note:** This is only reported on WASM.
+    #[error("WebUSB is not supported in this browser")]
+    NotSupported,
+}
+
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        std::io::Error::new(err.io_kind(), err)
+    }
+}
+
+impl Error {
+    /// `source`, or a [`Error::PartialTransfer`] wrapping it if any bytes
+    /// were written before it happened.
+    pub(crate) fn partial(written: usize, source: Error) -> Error {
+        if written == 0 {
+            return source;
+        }
+
+        Error::PartialTransfer {
+            written,
+            source: Box::new(source),
+        }
+    }
+
+    fn io_kind(&self) -> std::io::ErrorKind {
+        match self {
+            Error::DeviceNotFound | Error::EndpointNotFound(_) | Error::InterfaceNotFound => {
+                std::io::ErrorKind::NotFound
+            }
+            Error::TransferError | Error::Stall => std::io::ErrorKind::BrokenPipe,
+            Error::CommunicationError(_) | Error::AmbiguousDevice | Error::AmbiguousInterface(_) => {
+                std::io::ErrorKind::Other
+            }
+            Error::Babble | Error::InvalidDescriptor => std::io::ErrorKind::InvalidData,
+            Error::Disconnected | Error::Invalid => std::io::ErrorKind::NotConnected,
+            Error::Cancelled => std::io::ErrorKind::ConnectionAborted,
+            Error::Read { kind, .. } => *kind,
+            Error::Timeout => std::io::ErrorKind::TimedOut,
+            Error::InterfacesClaimed | Error::Busy { .. } => std::io::ErrorKind::ResourceBusy,
+            Error::PermissionDenied => std::io::ErrorKind::PermissionDenied,
+            Error::NotSupported => std::io::ErrorKind::Unsupported,
+            Error::PartialTransfer { source, .. } | Error::Transaction { source, .. } => source.io_kind(),
+        }
+    }
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl embedded_io_async::Error for Error {
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        match self {
+            Error::DeviceNotFound | Error::EndpointNotFound(_) | Error::InterfaceNotFound => {
+                embedded_io_async::ErrorKind::NotFound
+            }
+            Error::TransferError | Error::Stall => embedded_io_async::ErrorKind::BrokenPipe,
+            Error::CommunicationError(_) | Error::AmbiguousDevice | Error::AmbiguousInterface(_) => {
+                embedded_io_async::ErrorKind::Other
+            }
+            Error::Babble | Error::InvalidDescriptor => embedded_io_async::ErrorKind::InvalidData,
+            Error::Disconnected | Error::Invalid => embedded_io_async::ErrorKind::NotConnected,
+            Error::Cancelled => embedded_io_async::ErrorKind::ConnectionAborted,
+            Error::Read { .. } => embedded_io_async::ErrorKind::Other,
+            Error::Timeout => embedded_io_async::ErrorKind::TimedOut,
+            Error::InterfacesClaimed | Error::Busy { .. } => embedded_io_async::ErrorKind::Other,
+            Error::PermissionDenied => embedded_io_async::ErrorKind::PermissionDenied,
+            Error::NotSupported => embedded_io_async::ErrorKind::Unsupported,
+            Error::PartialTransfer { source, .. } | Error::Transaction { source, .. } => source.kind(),
+        }
+    }
+}
+
+/// The half of a [`crate::Interface::bulk_transaction`] which failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TransactionStage {
+    /// Sending the command to the OUT endpoint.
+    Write,
+
+    /// Reading the reply from the IN endpoint.
+    Read,
+}
+
+impl std::fmt::Display for TransactionStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionStage::Write => write!(f, "write"),
+            TransactionStage::Read => write!(f, "read"),
+        }
+    }
+}
+
+/// An error from parsing a [`crate::DeviceFilter`] from a string such as
+/// `054c:00c9`.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseFilterError {
+    /// Neither a vendor ID nor a product ID was given.
+    #[error("no vendor or product ID given")]
+    Empty,
+
+    /// The vendor ID isn't a 16 bit hexadecimal number.
+    #[error("invalid vendor ID `{0}`, expected up to four hexadecimal digits")]
+    InvalidVendorId(String),
+
+    /// The product ID isn't a 16 bit hexadecimal number.
+    #[error("invalid product ID `{0}`, expected up to four hexadecimal digits")]
+    InvalidProductId(String),
+
+    /// The serial number is empty, though the string has a third part for it.
+    #[error("empty serial number")]
+    EmptySerialNumber,
+}
+
+/// Identifies a physical device, so that it can be found again later with
+/// [`crate::open_by_id`], even after the program restarts.
+///
+/// Get one from [`crate::DeviceInfo::id`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceId {
+    /// The vendor ID of the device.
+    pub vendor_id: u16,
+
+    /// The product ID of the device.
+    pub product_id: u16,
+
+    /// The serial number of the device, if it has one.
+    pub serial_number: Option<String>,
+
+    /// The bus the device is connected to, if known.
+    pub bus_number: Option<u8>,
+
+    /// The ports between the root hub and the device, if known. See
+    /// [`UsbDeviceInfo::port_chain`].
+    pub port_chain: Option<Vec<u8>>,
+}
+
+/// What works on the current target, from [`crate::capabilities`], so that
+/// code which runs on several targets can fall back when something isn't
+/// available instead of checking for targets itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Whether every connected device can be listed without asking the user.
+    /// On WASM only devices the page has been given access to are listed.
+    pub enumerate_without_prompt: bool,
+
+    /// Whether [`UsbDevice::detach_and_open_interface`] detaches kernel
+    /// drivers, rather than just opening the interface.
+    pub detach_kernel_driver: bool,
+
+    /// Whether transfers which time out or are cancelled are aborted, as
+    /// with [`UsbInterface::can_abort_transfers`].
+    pub abort_transfers: bool,
+
+    /// Whether [`UsbDeviceInfo::speed`] reports the speed of devices.
+    pub speed: bool,
+
+    /// Whether [`UsbDeviceInfo::usb_version`] reports the USB version of
+    /// devices.
+    pub usb_version: bool,
+
+    /// Whether [`UsbDeviceInfo::port_chain`] reports where devices are
+    /// plugged in.
+    pub port_chain: bool,
+
+    /// Whether descriptors can be read from a device before any of its
+    /// interfaces are claimed.
+    pub device_control_transfers: bool,
+
+    /// Whether devices being connected and disconnected are reported by
+    /// [`crate::watch_devices`].
+    pub hotplug: bool,
+
+    /// Whether interrupt transfers are supported.
+    pub interrupt_transfers: bool,
+
+    /// Whether isochronous transfers are supported.
+    pub isochronous_transfers: bool,
+}
+
+/// Whether the page may use USB devices, from [`crate::permission_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PermissionState {
+    /// The page may use USB devices. This is always the state on native.
+    Granted,
+
+    /// The user will be asked when the page requests a device.
+    Prompt,
+
+    /// The page may not use USB devices, such as when a permissions policy
+    /// blocks WebUSB.
+    Denied,
+
+    /// The browser can't say, because it doesn't support querying the `usb`
+    /// permission.
+    Unsupported,
+}
+
+/// How [`crate::get_device_with_options`] may find a device.
+///
+/// The default reuses a device the page already has access to if one
+/// matches, and otherwise asks the user to pick one, as
+/// [`crate::get_device`] does.
+///
+/// **Note:** These only make a difference on WASM. On native there is no
+/// chooser, and every connected device can be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetDeviceOptions {
+    /// Whether the browser's device chooser may be shown. When this is
+    /// `false` the chooser is never shown, so no user gesture is needed.
+    pub allow_prompt: bool,
+
+    /// Whether to return a matching device the page already has access to
+    /// instead of asking the user. When this is `false` the user is always
+    /// asked to pick a device.
+    pub prefer_paired: bool,
+}
+
+impl Default for GetDeviceOptions {
+    fn default() -> Self {
+        Self {
+            allow_prompt: true,
+            prefer_paired: true,
+        }
+    }
+}
+
+/// An owned copy of the identifying information of a device, from
+/// [`crate::Device::info`], which can be kept after the device is closed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceInfoSnapshot {
+    /// The vendor ID of the device.
+    pub vendor_id: u16,
+
+    /// The product ID of the device.
+    pub product_id: u16,
+
+    /// The class code of the device.
+    pub class: u8,
+
+    /// The subclass code of the device.
+    pub subclass: u8,
+
+    /// The protocol code of the device.
+    pub protocol: u8,
+
+    /// The release number of the device.
+    pub device_version: Version,
+
+    /// The manufacturer string of the device, if available.
+    pub manufacturer_string: Option<String>,
+
+    /// The product string of the device, if available.
+    pub product_string: Option<String>,
+
+    /// The serial number of the device, if available.
+    pub serial_number_string: Option<String>,
+
+    /// The bus the device is connected to, if known.
+    pub bus_number: Option<u8>,
+
+    /// The address of the device on its bus, if known.
+    pub device_address: Option<u8>,
+
+    /// The ports between the root hub and the device, if known.
+    pub port_chain: Option<Vec<u8>>,
+}
+
+/// The device descriptor, which describes a device as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceDescriptor {
+    /// The length of the descriptor in bytes (`bLength`), which is 18.
+    pub length: u8,
+
+    /// The type of the descriptor (`bDescriptorType`), which is 1.
+    pub descriptor_type: u8,
+
+    /// The version of the USB specification the device supports (`bcdUSB`).
+    pub usb_version: Version,
+
+    /// The class code of the device (`bDeviceClass`).
+    pub class: u8,
+
+    /// The subclass code of the device (`bDeviceSubClass`).
+    pub subclass: u8,
+
+    /// The protocol code of the device (`bDeviceProtocol`).
+    pub protocol: u8,
+
+    /// The largest packet endpoint 0 sends or receives (`bMaxPacketSize0`).
+    pub max_packet_size_0: u8,
+
+    /// The vendor ID of the device (`idVendor`).
+    pub vendor_id: u16,
+
+    /// The product ID of the device (`idProduct`).
+    pub product_id: u16,
+
+    /// The release number of the device (`bcdDevice`).
+    pub device_version: Version,
+
+    /// The index of the manufacturer string (`iManufacturer`), if it has one.
+    pub manufacturer_string_index: Option<u8>,
+
+    /// The index of the product string (`iProduct`), if it has one.
+    pub product_string_index: Option<u8>,
+
+    /// The index of the serial number string (`iSerialNumber`), if it has one.
+    pub serial_number_string_index: Option<u8>,
+
+    /// The number of configurations of the device (`bNumConfigurations`).
+    pub num_configurations: u8,
+}
+
+/// The Binary device Object Store (BOS) descriptor, which lists the
+/// capabilities of a device beyond what the device descriptor describes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BosDescriptor {
+    /// The device capabilities, in the order the device lists them.
+    pub capabilities: Vec<DeviceCapability>,
+}
+
+impl BosDescriptor {
+    /// The WebUSB platform capability, if the device has one.
+    pub fn webusb(&self) -> Option<WebUsbPlatform> {
+        self.capabilities.iter().find_map(|capability| match capability {
+            DeviceCapability::Platform { uuid, data } if *uuid == WebUsbPlatform::UUID => {
+                WebUsbPlatform::parse(data)
+            }
+            _ => None,
+        })
+    }
+
+    /// The Microsoft OS 2.0 platform capability, if the device has one.
+    pub fn ms_os_20(&self) -> Option<MsOs20Platform> {
+        self.capabilities.iter().find_map(|capability| match capability {
+            DeviceCapability::Platform { uuid, data } if *uuid == MsOs20Platform::UUID => {
+                MsOs20Platform::parse(data)
+            }
+            _ => None,
+        })
+    }
+}
+
+/// A device capability descriptor from the [`BosDescriptor`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceCapability {
+    /// The USB 2.0 extension capability, describing Link Power Management
+    /// support.
+    Usb2Extension {
+        /// The attributes bitmap (`bmAttributes`).
+        attributes: u32,
+    },
+
+    /// The SuperSpeed USB capability.
+    SuperSpeed {
+        /// The attributes bitmap (`bmAttributes`).
+        attributes: u8,
+
+        /// The bitmap of speeds the device supports (`wSpeedsSupported`).
+        speeds_supported: u16,
+
+        /// The lowest speed at which all of its functionality is available
+        /// (`bFunctionalitySupport`).
+        functionality_support: u8,
+
+        /// The U1 exit latency in µs (`bU1DevExitLat`).
+        u1_exit_latency: u8,
+
+        /// The U2 exit latency in µs (`wU2DevExitLat`).
+        u2_exit_latency: u16,
+    },
+
+    /// A platform capability, identified by its UUID.
+    Platform {
+        /// The UUID of the platform (`PlatformCapabilityUUID`), in the byte
+        /// order it has in the descriptor.
+        uuid: [u8; 16],
+
+        /// The platform specific data which follows the UUID.
+        data: Vec<u8>,
+    },
+
+    /// Any other capability.
+    Other {
+        /// The type of the capability (`bDevCapabilityType`).
+        capability_type: u8,
+
+        /// The data which follows the capability type.
+        data: Vec<u8>,
+    },
+}
+
+/// The WebUSB platform capability, which points to the landing page of the
+/// device and the vendor request used to read WebUSB descriptors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WebUsbPlatform {
+    /// The version of the WebUSB specification (`bcdVersion`).
+    pub version: Version,
+
+    /// The `bRequest` value used for WebUSB requests (`bVendorCode`).
+    pub vendor_code: u8,
+
+    /// The index of the URL descriptor of the landing page (`iLandingPage`),
+    /// if it has one.
+    pub landing_page_index: Option<u8>,
+}
+
+impl WebUsbPlatform {
+    /// `{3408b638-09a9-47a0-8bfd-a0768815b665}`, in descriptor byte order.
+    const UUID: [u8; 16] = [
+        0x38, 0xB6, 0x08, 0x34, 0xA9, 0x09, 0xA0, 0x47, 0x8B, 0xFD, 0xA0, 0x76, 0x88, 0x15, 0xB6,
+        0x65,
+    ];
+
+    fn parse(data: &[u8]) -> Option<Self> {
+        let [version_low, version_high, vendor_code, landing_page, ..] = *data else {
+            return None;
+        };
+
+        Some(Self {
+            version: Version::from_bcd(u16::from_le_bytes([version_low, version_high])),
+            vendor_code,
+            landing_page_index: (landing_page != 0).then_some(landing_page),
+        })
+    }
+}
+
+/// The Microsoft OS 2.0 platform capability, which tells Windows how to read
+/// the [`MsOsDescriptorSet`] of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MsOs20Platform {
+    /// The minimum Windows version the descriptor set applies to
+    /// (`dwWindowsVersion`).
+    pub windows_version: u32,
+
+    /// The length of the descriptor set (`wMSOSDescriptorSetTotalLength`).
+    pub total_length: u16,
+
+    /// The `bRequest` value used to read the descriptor set
+    /// (`bMS_VendorCode`).
+    pub vendor_code: u8,
+
+    /// The code used to select alternate enumeration, or 0 if the device
+    /// doesn't support it (`bAltEnumCode`).
+    pub alt_enum_code: u8,
+}
+
+impl MsOs20Platform {
+    /// `{d8dd60df-4589-4cc7-9cd2-659d9e648a9f}`, in descriptor byte order.
+    const UUID: [u8; 16] = [
+        0xDF, 0x60, 0xDD, 0xD8, 0x89, 0x45, 0xC7, 0x4C, 0x9C, 0xD2, 0x65, 0x9D, 0x9E, 0x64, 0x8A,
+        0x9F,
+    ];
+
+    fn parse(data: &[u8]) -> Option<Self> {
+        let [v0, v1, v2, v3, length_low, length_high, vendor_code, alt_enum_code, ..] = *data else {
+            return None;
+        };
+
+        Some(Self {
+            windows_version: u32::from_le_bytes([v0, v1, v2, v3]),
+            total_length: u16::from_le_bytes([length_low, length_high]),
+            vendor_code,
+            alt_enum_code,
+        })
+    }
+}
+
+/// The Microsoft OS 2.0 descriptor set of a device, which Windows uses to
+/// decide which driver to bind to it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MsOsDescriptorSet {
+    /// The minimum Windows version the set applies to (`dwWindowsVersion`).
+    pub windows_version: u32,
+
+    /// The feature descriptors in the set, in the order the device lists
+    /// them. Configuration and function subsets are flattened, with the
+    /// interface each descriptor applies to recorded in it.
+    pub descriptors: Vec<MsOsDescriptor>,
+}
+
+/// A feature descriptor from a [`MsOsDescriptorSet`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MsOsDescriptor {
+    /// The compatible ID, such as `"WINUSB"`, which selects the driver.
+    CompatibleId {
+        /// The first interface of the function this applies to, or `None`
+        /// if it applies to the whole device.
+        interface: Option<u8>,
+
+        /// The compatible ID, without its padding.
+        compatible_id: String,
+
+        /// The sub-compatible ID, without its padding.
+        sub_compatible_id: String,
+    },
+
+    /// A registry property, such as `DeviceInterfaceGUIDs`.
+    RegistryProperty {
+        /// The first interface of the function this applies to, or `None`
+        /// if it applies to the whole device.
+        interface: Option<u8>,
+
+        /// The type of the property (`wPropertyDataType`), such as 1 for
+        /// `REG_SZ` or 7 for `REG_MULTI_SZ`.
+        data_type: u16,
+
+        /// The name of the property.
+        name: String,
+
+        /// The raw value of the property.
+        data: Vec<u8>,
+    },
+
+    /// Any other feature descriptor.
+    Other {
+        /// The first interface of the function this applies to, or `None`
+        /// if it applies to the whole device.
+        interface: Option<u8>,
+
+        /// The type of the descriptor (`wDescriptorType`).
+        descriptor_type: u16,
+
+        /// The data which follows the descriptor type.
+        data: Vec<u8>,
+    },
+}
+
+/// A configuration of a device, from its configuration descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfigurationInfo {
+    /// The value used to select this configuration (`bConfigurationValue`).
+    pub value: u8,
+
+    /// The index of the string descriptor describing this configuration
+    /// (`iConfiguration`), if it has one.
+    pub string_index: Option<u8>,
+
+    /// The number of interfaces in this configuration (`bNumInterfaces`).
+    pub num_interfaces: u8,
+
+    /// The attributes bitmap of this configuration (`bmAttributes`).
+    pub attributes: u8,
+
+    /// The maximum power drawn in this configuration (`bMaxPower`), in units
+    /// of 2 mA for USB 2.0 devices or 8 mA for SuperSpeed devices.
+    pub max_power: u8,
+
+    /// Whether the device is powered by its own supply in this configuration,
+    /// from the attributes bitmap.
+    pub self_powered: bool,
+
+    /// The maximum current drawn from the bus in this configuration in mA,
+    /// converted from [`ConfigurationInfo::max_power`] for the speed the
+    /// device is connected at.
+    ///
+    /// When the speed isn't known, which is always the case on WASM, this is
+    /// an approximation from the USB version the device supports instead:
+    /// units of 8 mA from USB 3.0 on, and 2 mA before. It's four times too
+    /// high for a USB 3 device which is connected at USB 2 speeds. This is
+    /// `None` if neither is known.
+    pub max_power_ma: Option<u32>,
+}
+
+/// An interface of a device, from the interface descriptors of the active
+/// configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterfaceInfo {
+    /// The number used to open this interface (`bInterfaceNumber`).
+    pub number: u8,
+
+    /// The class code of this interface (`bInterfaceClass`).
+    pub class: u8,
+
+    /// The subclass code of this interface (`bInterfaceSubClass`).
+    pub subclass: u8,
+
+    /// The protocol code of this interface (`bInterfaceProtocol`).
+    pub protocol: u8,
+
+    /// The index of the string descriptor describing this interface
+    /// (`iInterface`), if it has one.
+    pub string_index: Option<u8>,
+
+    /// The number of alternate settings of this interface, including the
+    /// default setting.
+    pub num_alt_settings: u8,
+}
+
+/// A function of a composite device, made up of one or more interfaces which
+/// the host should use together, such as the control and data interfaces of
+/// a CDC serial port.
+///
+/// Interfaces grouped by an interface association descriptor form one
+/// function, and every other interface is a function of its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionInfo {
+    /// The numbers of the interfaces which make up this function, in
+    /// increasing order.
+    pub interfaces: Vec<u8>,
+
+    /// The class code of this function (`bFunctionClass`, or
+    /// `bInterfaceClass` for a single interface).
+    pub class: u8,
+
+    /// The subclass code of this function (`bFunctionSubClass`, or
+    /// `bInterfaceSubClass` for a single interface).
+    pub subclass: u8,
+
+    /// The protocol code of this function (`bFunctionProtocol`, or
+    /// `bInterfaceProtocol` for a single interface).
+    pub protocol: u8,
+
+    /// The index of the string descriptor describing this function
+    /// (`iFunction`, or `iInterface` for a single interface), if it has one.
+    pub string_index: Option<u8>,
+}
+
+/// An alternate setting of an interface, from its interface descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AltSettingInfo {
+    /// The value used to select this setting (`bAlternateSetting`).
+    pub alt_setting: u8,
+
+    /// The class code of this setting (`bInterfaceClass`).
+    pub class: u8,
+
+    /// The subclass code of this setting (`bInterfaceSubClass`).
+    pub subclass: u8,
+
+    /// The protocol code of this setting (`bInterfaceProtocol`).
+    pub protocol: u8,
+
+    /// The number of endpoints in this setting, not counting endpoint 0
+    /// (`bNumEndpoints`).
+    pub num_endpoints: u8,
+}
+
+/// An endpoint of an interface, from its endpoint descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EndpointInfo {
+    /// The address of this endpoint, including the direction bit
+    /// (`bEndpointAddress`). This is what transfers take as their `endpoint`.
+    pub address: u8,
+
+    /// The direction of this endpoint.
+    pub direction: Direction,
+
+    /// The type of transfers this endpoint is used for.
+    pub transfer_type: TransferType,
+
+    /// The largest packet this endpoint sends or receives, in bytes.
+    pub max_packet_size: usize,
+
+    /// The raw polling interval of this endpoint (`bInterval`), whose
+    /// meaning depends on the transfer type and the speed of the device.
+    ///
+    /// **Note:** WebUSB doesn't expose this, so it is always `None` on WASM.
+    pub interval: Option<u8>,
+
+    /// The time between polls of an interrupt or isochronous endpoint in
+    /// microseconds, decoded from [`EndpointInfo::interval`]. This is `None`
+    /// for control and bulk endpoints, or if the speed of the device isn't
+    /// known.
+    pub interval_micros: Option<u32>,
+}
+
+/// The direction of an endpoint, from the host's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    /// Device to host
+    In,
+
+    /// Host to device
+    Out,
+}
+
+/// The type of transfers an endpoint is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransferType {
+    /// Control transfers
+    Control,
+
+    /// Isochronous transfers
+    Isochronous,
+
+    /// Bulk transfers
+    Bulk,
+
+    /// Interrupt transfers
+    Interrupt,
+}
+
+/// An interface of a device which hasn't been opened, as reported by the OS
+/// in [`UsbDeviceInfo::interfaces`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterfaceFilterInfo {
+    /// The number used to open this interface (`bInterfaceNumber`).
+    pub number: u8,
+
+    /// The class code of this interface (`bInterfaceClass`).
+    pub class: u8,
+
+    /// The subclass code of this interface (`bInterfaceSubClass`).
+    pub subclass: u8,
+
+    /// The protocol code of this interface (`bInterfaceProtocol`).
+    pub protocol: u8,
+
+    /// The string describing this interface (`iInterface`), if it has one
+    /// and the OS has read it.
+    pub interface_string: Option<String>,
+}
+
+/// The status of a device, from [`crate::Device::status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceStatus {
+    /// Whether the device is currently powered by its own supply rather than
+    /// the bus.
+    pub self_powered: bool,
+
+    /// Whether the device is allowed to wake the host.
+    pub remote_wakeup: bool,
+}
+
+/// The status of an interface, from [`crate::Interface::status`]. This is
+/// only used by SuperSpeed devices, and is all `false` for others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InterfaceStatus {
+    /// Whether the function the interface belongs to can wake the host.
+    pub remote_wake_capable: bool,
+
+    /// Whether the function the interface belongs to is allowed to wake the
+    /// host.
+    pub remote_wakeup: bool,
+}
+
+/// The status of an endpoint, from [`crate::Interface::endpoint_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EndpointStatus {
+    /// Whether the endpoint is halted, which is cleared with
+    /// [`UsbInterface::clear_halt`].
+    pub halted: bool,
+}
+
+/// The speed a USB device is connected at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Speed {
+    /// Low speed (1.5 Mbit/s)
+    Low,
+
+    /// Full speed (12 Mbit/s)
+    Full,
+
+    /// High speed (480 Mbit/s)
+    High,
+
+    /// SuperSpeed (5 Gbit/s)
+    Super,
+
+    /// SuperSpeed+ (10 Gbit/s or more)
+    SuperPlus,
+}
+
+/// A version number in the binary-coded decimal format used by USB
+/// descriptors, such as the `bcdDevice` field of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Version {
+    /// The major version, from 0 to 99.
+    pub major: u8,
+
+    /// The minor version, from 0 to 9.
+    pub minor: u8,
+
+    /// The subminor version, from 0 to 9.
+    pub subminor: u8,
+}
+
+impl Version {
+    /// Create a version from its parts.
+    pub fn new(major: u8, minor: u8, subminor: u8) -> Self {
+        Self {
+            major,
+            minor,
+            subminor,
+        }
+    }
+
+    /// Parse a version from its binary-coded decimal form `0xJJMN`, where
+    /// `JJ` is the major version, `M` the minor and `N` the subminor version.
+    ///
+    /// ```
+    /// use cross_usb::usb::Version;
+    ///
+    /// assert_eq!(Version::from_bcd(0x0210), Version::new(2, 1, 0));
+    /// assert_eq!(Version::from_bcd(0x1234), Version::new(12, 3, 4));
+    /// assert_eq!(Version::from_bcd(0x0001), Version::new(0, 0, 1));
+    /// assert_eq!(Version::from_bcd(0x9999), Version::new(99, 9, 9));
+    /// ```
+    pub fn from_bcd(bcd: u16) -> Self {
+        let [high, low] = bcd.to_be_bytes();
+
+        Self {
+            major: (high >> 4) * 10 + (high & 0x0F),
+            minor: low >> 4,
+            subminor: low & 0x0F,
+        }
+    }
+
+    /// Convert the version back into its binary-coded decimal form.
+    ///
+    /// ```
+    /// use cross_usb::usb::Version;
+    ///
+    /// assert_eq!(Version::new(2, 1, 0).to_bcd(), 0x0210);
+    /// assert_eq!(Version::new(12, 3, 4).to_bcd(), 0x1234);
+    /// assert_eq!(Version::from_bcd(0x0520).to_bcd(), 0x0520);
+    /// ```
+    pub fn to_bcd(&self) -> u16 {
+        let high = ((self.major / 10) << 4) | (self.major % 10);
+        let low = (self.minor << 4) | (self.subminor & 0x0F);
+
+        u16::from_be_bytes([high, low])
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.subminor)
+    }
+}
+
+/// The outcome of a successful transfer, returned by
+/// [`UsbInterface::bulk_in_result`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferResult {
+    /// The bytes which were received.
+    pub data: Vec<u8>,
+
+    /// Whether the transfer ended on a short packet, returning fewer bytes
+    /// than were requested.
+    pub short_packet: bool,
+
+    /// How the transfer completed.
+    pub status: TransferStatus,
+}
+
+/// How a successful transfer completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TransferStatus {
+    /// The transfer completed normally.
+    Completed,
+
+    /// The device sent more data than was requested, so the data was
+    /// truncated to the requested length. Other transfers fail with
+    /// [`Error::Babble`] instead.
+    ///
+    /// **Note:** This is only reported on WASM. Native platforms report
+    /// this as an [`Error::TransferError`] instead.
+    Babble,
+}
+
+/// When and how often to retry transfers which fail, set with
+/// [`UsbInterface::set_retry_policy`].
+///
+/// Transfers which fail with [`Error::Stall`], [`Error::Disconnected`],
+/// [`Error::Cancelled`] or [`Error::PartialTransfer`] are never retried,
+/// whatever `retry_on` says, since repeating them wouldn't help or would send
+/// data twice. Neither are OUT transfers which fail with [`Error::Timeout`],
+/// as their data may still have reached the device.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// The maximum number of times a transfer is attempted, including the
+    /// first. `1` (the default) disables retrying.
+    pub max_attempts: u32,
+
+    /// How long to wait before each retry.
+    pub backoff: Duration,
+
+    /// Whether a failed transfer should be retried. Defaults to
+    /// [`RetryPolicy::transient`].
+    pub retry_on: fn(&Error) -> bool,
+}
+
+impl RetryPolicy {
+    /// A policy which makes up to `max_attempts` attempts, waiting `backoff`
+    /// between them, and retries [`RetryPolicy::transient`] errors.
+    pub fn new(max_attempts: u32, backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            backoff,
+            retry_on: Self::transient,
+        }
+    }
+
+    /// Returns `true` for [`Error::Timeout`] and [`Error::TransferError`],
+    /// which are often caused by a device that is briefly busy. OUT transfers
+    /// which time out are still not retried.
+    pub fn transient(err: &Error) -> bool {
+        matches!(err, Error::Timeout | Error::TransferError)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(1, Duration::ZERO)
+    }
 }
 
 /// The type of USB control transfer.
+#[derive(Clone, Copy)]
 pub enum ControlType {
     /// A standard transfer.
     Standard = 0,
@@ -147,6 +1620,7 @@ pub enum ControlType {
 }
 
 /// The recipient of a USB transfer.
+#[derive(Clone, Copy)]
 pub enum Recipient {
     /// The device is the recipient.
     Device = 0,
@@ -162,6 +1636,7 @@ pub enum Recipient {
 }
 
 /// Parameters for [UsbInterface::control_in].
+#[derive(Clone, Copy)]
 pub struct ControlIn {
     /// The [`ControlType`] of this transfer, in the `bmRequestType` field.
     pub control_type: ControlType,
@@ -183,6 +1658,7 @@ pub struct ControlIn {
 }
 
 /// Parameters for [UsbInterface::control_out].
+#[derive(Clone, Copy)]
 pub struct ControlOut<'a> {
     /// The [`ControlType`] of this transfer, in the `bmRequestType` field.
     pub control_type: ControlType,
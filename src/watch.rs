@@ -0,0 +1,362 @@
+//! Watching for devices being connected and disconnected, the same way on
+//! every backend.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use futures_timer::Delay;
+
+use crate::context::{DeviceKey, Hotplug, HotplugEvent};
+use crate::timer;
+use crate::usb::{DeviceId, Error};
+use crate::{filter, Device, DeviceFilter, DeviceInfo};
+
+/// How often devices are checked on when they can't be watched.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A device matching the filters of a [`DeviceWatch`] being connected or
+/// disconnected.
+#[derive(Debug)]
+pub enum DeviceEvent {
+    /// A matching device was connected, and can be opened.
+    Connected(DeviceInfo),
+
+    /// A matching device was disconnected. This is the ID it was connected
+    /// with, which can be compared to [`DeviceInfo::id`].
+    Disconnected(DeviceId),
+}
+
+#[cfg(not(target_family = "wasm"))]
+type Pending = Pin<Box<dyn Future<Output = Step> + Send>>;
+
+// Futures on WASM hold JS values, which can't be sent between threads
+#[cfg(target_family = "wasm")]
+type Pending = Pin<Box<dyn Future<Output = Step>>>;
+
+/// The result of the work a [`DeviceWatch`] does between events.
+enum Step {
+    /// The matching devices which were connected when watching started.
+    Listed(Vec<(DeviceKey, DeviceId)>),
+
+    /// A device was connected, along with its ID if it matches the filters.
+    Connected(DeviceInfo, Option<DeviceId>),
+}
+
+/// A stream of [`DeviceEvent`]s for the devices matching a set of filters,
+/// from [`watch_devices`].
+///
+/// Dropping this stops watching for devices.
+pub struct DeviceWatch {
+    hotplug: Hotplug,
+    device_filters: Vec<DeviceFilter>,
+    connected: Vec<(DeviceKey, DeviceId)>,
+    pending: Option<Pending>,
+}
+
+impl std::fmt::Debug for DeviceWatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceWatch")
+            .field("device_filters", &self.device_filters)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Stream for DeviceWatch {
+    type Item = DeviceEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(pending) = &mut this.pending {
+                let Poll::Ready(step) = pending.as_mut().poll(cx) else {
+                    return Poll::Pending;
+                };
+                this.pending = None;
+
+                match step {
+                    Step::Listed(devices) => this.connected.extend(devices),
+                    Step::Connected(device_info, Some(id)) => {
+                        // Devices connected while the list was being made
+                        // show up in it too
+                        let key = device_info.key();
+                        if !this.connected.iter().any(|(connected, _)| *connected == key) {
+                            this.connected.push((key, id));
+                            return Poll::Ready(Some(DeviceEvent::Connected(device_info)));
+                        }
+                    }
+                    Step::Connected(_, None) => {}
+                }
+
+                continue;
+            }
+
+            match this.hotplug.poll_next(cx) {
+                Poll::Ready(Some(HotplugEvent::Connected(device_info))) => {
+                    this.pending = Some(Box::pin(identify(this.device_filters.clone(), device_info)));
+                }
+                Poll::Ready(Some(HotplugEvent::Disconnected(key))) => {
+                    // Only devices which matched are tracked, so others are
+                    // skipped here
+                    let Some(index) = this.connected.iter().position(|(connected, _)| *connected == key) else {
+                        continue;
+                    };
+
+                    let (_, id) = this.connected.remove(index);
+                    return Poll::Ready(Some(DeviceEvent::Disconnected(id)));
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// The matching devices which are already connected, so that they are
+/// reported when they are disconnected.
+async fn list(device_filters: Vec<DeviceFilter>) -> Step {
+    // Without the list, devices are still reported once they are reconnected
+    let Ok(devices) = crate::context::list_devices().await else {
+        return Step::Listed(Vec::new());
+    };
+
+    let mut connected = Vec::new();
+    for device_info in devices {
+        if filter::accepts(&device_filters, &device_info, &|_| true).await {
+            connected.push((device_info.key(), device_info.id().await));
+        }
+    }
+
+    Step::Listed(connected)
+}
+
+/// Check a newly connected device against `device_filters`.
+async fn identify(device_filters: Vec<DeviceFilter>, device_info: DeviceInfo) -> Step {
+    if filter::accepts(&device_filters, &device_info, &|_| true).await {
+        let id = device_info.id().await;
+        Step::Connected(device_info, Some(id))
+    } else {
+        Step::Connected(device_info, None)
+    }
+}
+
+/// Watch for devices matching any of `device_filters` being connected or
+/// disconnected, as a [`Stream`] of [`DeviceEvent`]s. Filters are matched
+/// as in [`crate::get_device_with`], so a list without filters which include
+/// devices, such as an empty list, watches every device which isn't excluded.
+///
+/// Devices which are already connected when watching starts aren't reported
+/// as connected, only once they are disconnected. Dropping the stream stops
+/// watching.
+///
+/// **Note:** On WASM, only devices the page has already been given access to
+/// are reported, as the browser doesn't tell the page about others.
+///
+/// **Note:** On Windows, the interfaces of a composite device might not be
+/// ready as soon as it's reported as connected, so claiming one may need to
+/// be retried after a short delay.
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use cross_usb::prelude::*;
+/// use cross_usb::{device_filter, DeviceEvent};
+/// use futures::StreamExt;
+///
+/// let filter = vec![device_filter!{vendor_id: 0x054c, product_id: 0x00c9}];
+/// let mut events = cross_usb::watch_devices(filter).expect("Failed to watch devices");
+///
+/// println!("Plug in your device now...");
+/// while let Some(event) = events.next().await {
+///     match event {
+///         DeviceEvent::Connected(device_info) => {
+///             let device = device_info.open().await.expect("Failed to open device");
+///             break;
+///         }
+///         DeviceEvent::Disconnected(id) => println!("{:04x}:{:04x} was unplugged", id.vendor_id, id.product_id),
+///     }
+/// }
+/// # })
+/// ```
+pub fn watch_devices(device_filters: Vec<DeviceFilter>) -> Result<DeviceWatch, Error> {
+    // Start watching before listing the devices, so none are missed between
+    // the two
+    let hotplug = Hotplug::new()?;
+    let pending: Pending = Box::pin(list(device_filters.clone()));
+
+    Ok(DeviceWatch {
+        hotplug,
+        device_filters,
+        connected: Vec::new(),
+        pending: Some(pending),
+    })
+}
+
+/// Wait until a device matching any of `device_filters` is connected, and
+/// return it. A matching device which is already connected is returned
+/// straight away. Filters are matched as in [`watch_devices`].
+///
+/// Fails with [`Error::Timeout`] if `timeout` is set and passes before a
+/// device is found. Where devices can't be watched, this checks for them
+/// periodically instead.
+///
+/// **Note:** On WASM, this only finds devices the page has already been
+/// given access to, such as one which was picked before and is plugged back
+/// in. A device the user hasn't picked yet still has to be requested with
+/// [`crate::request_device`].
+///
+/// ## Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use std::time::Duration;
+/// use cross_usb::prelude::*;
+/// use cross_usb::device_filter;
+///
+/// let filter = vec![device_filter!{vendor_id: 0x054c, product_id: 0x00c9}];
+///
+/// println!("Plug in your device now...");
+/// let device_info = cross_usb::wait_for_device(filter, Some(Duration::from_secs(30)))
+///     .await
+///     .expect("No device was plugged in");
+/// let device = device_info.open().await.expect("Failed to open device");
+/// # })
+/// ```
+pub async fn wait_for_device(
+    device_filters: Vec<DeviceFilter>,
+    timeout: Option<Duration>,
+) -> Result<DeviceInfo, Error> {
+    timer::bounded(timeout, async {
+        // Start watching before checking, so a device connected in between
+        // isn't missed
+        let watch = watch_devices(device_filters.clone());
+
+        if let Some(device_info) = find_connected(&device_filters).await? {
+            return Ok(device_info);
+        }
+
+        if let Ok(mut watch) = watch {
+            while let Some(event) = std::future::poll_fn(|cx| Pin::new(&mut watch).poll_next(cx)).await {
+                if let DeviceEvent::Connected(device_info) = event {
+                    return Ok(device_info);
+                }
+            }
+        }
+
+        loop {
+            Delay::new(POLL_INTERVAL).await;
+
+            if let Some(device_info) = find_connected(&device_filters).await? {
+                return Ok(device_info);
+            }
+        }
+    })
+    .await
+}
+
+/// The first connected device matching `device_filters`, if there is one.
+pub(crate) async fn find_connected(device_filters: &[DeviceFilter]) -> Result<Option<DeviceInfo>, Error> {
+    for device_info in crate::context::list_devices().await? {
+        if filter::accepts(device_filters, &device_info, &|_| true).await {
+            return Ok(Some(device_info));
+        }
+    }
+
+    Ok(None)
+}
+
+impl Device {
+    /// Whether the device is still connected, as far as is known, without
+    /// communicating with it. This is cheap enough to check before every
+    /// transfer.
+    ///
+    /// This becomes `false` once a transfer fails with
+    /// [`Error::Disconnected`], or a future from [`Device::on_disconnect`]
+    /// completes. Until then it can't tell that the device was unplugged.
+    pub fn is_connected(&self) -> bool {
+        self.connected().load(Ordering::Acquire)
+    }
+
+    /// Wait until the device is unplugged, without waiting for a transfer to
+    /// fail. The future doesn't borrow the device, so it can be run alongside
+    /// whatever is using it.
+    ///
+    /// Once the device is found to be unplugged, [`Device::is_connected`] is
+    /// `false`, and transfers which were in progress fail with
+    /// [`Error::Disconnected`]. Where devices can't be watched, this checks on
+    /// the device periodically instead.
+    ///
+    /// **Note:** On WASM, this also completes if the page's access to the
+    /// device is revoked.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// # use cross_usb::prelude::*;
+    /// let device = cross_usb::get_device(vec![]).await.unwrap().open().await.unwrap();
+    ///
+    /// let disconnected = device.on_disconnect();
+    /// disconnected.await;
+    /// assert!(!device.is_connected());
+    /// # })
+    /// ```
+    pub fn on_disconnect(&self) -> impl Future<Output = ()> {
+        let key = self.key();
+        let connected = self.connected().clone();
+
+        async move {
+            // Start watching before checking, so the device can't be
+            // unplugged unnoticed in between
+            let hotplug = Hotplug::new();
+
+            if !connected.load(Ordering::Acquire) || !is_listed(&key).await {
+                connected.store(false, Ordering::Release);
+                return;
+            }
+
+            if let Ok(mut hotplug) = hotplug {
+                while let Some(event) = std::future::poll_fn(|cx| hotplug.poll_next(cx)).await {
+                    if matches!(event, HotplugEvent::Disconnected(ref disconnected) if *disconnected == key) {
+                        connected.store(false, Ordering::Release);
+                        return;
+                    }
+                }
+            }
+
+            // Where devices can't be watched, check on the device
+            // periodically instead
+            while connected.load(Ordering::Acquire) && is_listed(&key).await {
+                Delay::new(POLL_INTERVAL).await;
+            }
+
+            connected.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// The error a transfer on a device failed with, given whether the device
+/// is known to be `connected`. A transfer which fails once the device is
+/// known to have been unplugged was cut short by it, whatever else was
+/// reported, and one which fails because it was unplugged marks it as gone.
+pub(crate) fn disconnect_error(connected: &AtomicBool, err: Error) -> Error {
+    match err {
+        Error::Disconnected => {
+            connected.store(false, Ordering::Release);
+            Error::Disconnected
+        }
+        _ if !connected.load(Ordering::Acquire) => Error::Disconnected,
+        err => err,
+    }
+}
+
+/// Whether the device identified by `key` is still listed. Devices are
+/// assumed to still be there if they can't be listed.
+async fn is_listed(key: &DeviceKey) -> bool {
+    match crate::context::list_devices().await {
+        Ok(devices) => devices.iter().any(|device_info| device_info.key() == *key),
+        Err(_) => true,
+    }
+}